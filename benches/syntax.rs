@@ -0,0 +1,22 @@
+// Times re-tokenizing a large Rust buffer after each keystroke
+use std::time::Instant;
+
+use text_editor::text::{Cursor, Text};
+
+const ITERATIONS: u32 = 50;
+
+fn main() {
+    let source = include_str!("../src/text.rs").repeat(20);
+    let mut text = Text::new();
+    text.load(Ok(source));
+    let mut cursor = Cursor::new((80, 24));
+    text.update_syntax();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        text.insert_char('x', &mut cursor);
+        text.update_syntax();
+    }
+    let elapsed = start.elapsed();
+    println!("update_syntax: {} lines, {:?} per keystroke", text.len(), elapsed / ITERATIONS);
+}
\ No newline at end of file
@@ -0,0 +1,88 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+/// Keeps the last `keep` versions of files in `dir`, as `name.1.bak` (the newest) up to `name.<keep>.bak`
+pub struct Backups {
+    pub dir: PathBuf,
+    pub keep: usize,
+}
+
+impl Backups {
+    pub fn new(dir: PathBuf, keep: usize) -> Self {
+        Self{dir, keep}
+    }
+
+    fn path(&self, file: &Path, number: usize) -> PathBuf {
+        let name = file.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        self.dir.join(format!("{}.{}.bak", name, number))
+    }
+
+    /// Moves each existing backup of `file` up a number, dropping any past the limit, then copies `file` in as the newest
+    pub fn backup(&self, file: &Path) -> io::Result<()> {
+        if self.keep == 0 || !file.is_file() {
+            return Ok(());
+        }
+        // The limit may have been lowered since the last save, so prune everything from it upwards
+        let mut number = self.keep;
+        while self.path(file, number).exists() {
+            fs::remove_file(self.path(file, number))?;
+            number += 1;
+        }
+        for number in (1..self.keep).rev() {
+            let path = self.path(file, number);
+            if path.exists() {
+                fs::rename(path, self.path(file, number + 1))?;
+            }
+        }
+        fs::copy(file, self.path(file, 1))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn read(path: PathBuf) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    #[test]
+    fn backups_rotate_and_are_pruned() {
+        let dir = env::temp_dir().join(format!("text_editor_backups_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.txt");
+        let backups = Backups::new(dir.join("backups"), 3);
+        fs::create_dir_all(&backups.dir).unwrap();
+        for version in 1..=5 {
+            fs::write(&file, format!("version {}", version)).unwrap();
+            backups.backup(&file).unwrap();
+        }
+        // The newest is .1, and only three are kept
+        assert_eq!(read(backups.path(&file, 1)).as_deref(), Some("version 5"));
+        assert_eq!(read(backups.path(&file, 2)).as_deref(), Some("version 4"));
+        assert_eq!(read(backups.path(&file, 3)).as_deref(), Some("version 3"));
+        assert_eq!(read(backups.path(&file, 4)), None);
+
+        // Lowering the limit prunes the ones past it on the next save
+        let backups = Backups::new(backups.dir, 2);
+        fs::write(&file, "version 6").unwrap();
+        backups.backup(&file).unwrap();
+        assert_eq!(read(backups.path(&file, 1)).as_deref(), Some("version 6"));
+        assert_eq!(read(backups.path(&file, 2)).as_deref(), Some("version 5"));
+        assert_eq!(read(backups.path(&file, 3)), None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn nothing_is_kept_for_a_new_file_or_with_a_limit_of_zero() {
+        let dir = env::temp_dir().join(format!("text_editor_no_backups_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("new.txt");
+        Backups::new(dir.clone(), 3).backup(&file).unwrap();
+        fs::write(&file, "contents").unwrap();
+        Backups::new(dir.clone(), 0).backup(&file).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
\ No newline at end of file
@@ -0,0 +1,57 @@
+use std::{fmt, io, path::PathBuf, process::{Command, Stdio}};
+
+/// An error or warning a build reported at a place in a file
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.path.display(), self.line + 1, self.column + 1, self.message.trim())
+    }
+}
+
+/// Runs a build command with the shell, returning whether it succeeded and everything it printed, errors included
+pub fn run(command: &str) -> io::Result<(bool, String)> {
+    let output = Command::new("sh").args(["-c", command]).stdin(Stdio::null()).output()?;
+    let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    Ok((output.status.success(), text))
+}
+
+/// Finds the diagnostics in a build's output: `path:line:col: message` lines, and rustc's, which give the message on one
+/// line and where it is on a `--> path:line:col` line after it
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut message = None;
+    for line in output.lines() {
+        if let Some(location) = line.trim_start().strip_prefix("--> ") {
+            if let Some((path, line, column)) = parse_location(location) {
+                diagnostics.push(Diagnostic{path, line, column, message: message.take().unwrap_or_default()});
+            }
+        } else if let Some(diagnostic) = parse_diagnostic_line(line) {
+            diagnostics.push(diagnostic);
+        } else if line.starts_with("error") || line.starts_with("warning") {
+            message = Some(line.to_string());
+        }
+    }
+    diagnostics
+}
+
+// A `path:line:col` place, counted from 1
+fn parse_location(location: &str) -> Option<(PathBuf, usize, usize)> {
+    let mut parts = location.trim().splitn(3, ':');
+    let path = parts.next().filter(|path| !path.is_empty())?;
+    let line: usize = parts.next()?.parse().ok()?;
+    let column: usize = parts.next()?.parse().ok()?;
+    Some((PathBuf::from(path), line.checked_sub(1)?, column.saturating_sub(1)))
+}
+
+// A `path:line:col: message` line
+fn parse_diagnostic_line(line: &str) -> Option<Diagnostic> {
+    let (location, message) = line.split_once(": ")?;
+    let (path, line, column) = parse_location(location)?;
+    Some(Diagnostic{path, line, column, message: message.to_string()})
+}
\ No newline at end of file
@@ -0,0 +1,110 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Works out an arithmetic expression of numbers, brackets, `+`, `-`, `*`, `/`, `%` and `^`, with the usual precedence
+pub fn evaluate(expression: &str) -> Result<f64, String> {
+    let mut parser = Parser{chars: expression.chars().peekable()};
+    let value = parser.sum()?;
+    match parser.next_char() {
+        None if !value.is_finite() => Err(String::from("Result is too large")),
+        None => Ok(value),
+        Some(c) => Err(format!("Unexpected '{}'", c)),
+    }
+}
+
+/// Writes a result the way it would be typed, so whole numbers have no decimal point and zero has no sign
+pub fn format(value: f64) -> String {
+    if value == 0.0 {String::from("0")} else {value.to_string()}
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    // The next character that isn't whitespace, without taking it
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        self.peek();
+        self.chars.next()
+    }
+
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        while let Some(operator @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            let operand = self.product()?;
+            value = if operator == '+' {value + operand} else {value - operand};
+        }
+        Ok(value)
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.power()?;
+        while let Some(operator @ ('*' | '/' | '%')) = self.peek() {
+            self.chars.next();
+            let operand = self.power()?;
+            if operator != '*' && operand == 0.0 {
+                return Err(String::from("Division by zero"));
+            }
+            value = match operator {
+                '*' => value * operand,
+                '/' => value / operand,
+                _ => value % operand,
+            };
+        }
+        Ok(value)
+    }
+
+    // Powers group to the right, so 2^3^2 is 2^9
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.unary()?;
+        if self.peek() == Some('^') {
+            self.chars.next();
+            return Ok(base.powf(self.power()?));
+        }
+        Ok(base)
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(-self.unary()?)
+            }
+            Some('+') => {
+                self.chars.next();
+                self.unary()
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.sum()?;
+                match self.next_char() {
+                    Some(')') => Ok(value),
+                    _ => Err(String::from("Missing ')'")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.' || *c == '_') {
+                    if c != '_' {
+                        number.push(c);
+                    }
+                }
+                number.parse().map_err(|_| format!("Not a number: {}", number))
+            }
+            Some(c) => Err(format!("Unexpected '{}'", c)),
+            None => Err(String::from("Expression ends too soon")),
+        }
+    }
+}
\ No newline at end of file
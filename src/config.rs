@@ -0,0 +1,334 @@
+use std::{collections::HashMap, env, fs, path::{Path, PathBuf}};
+
+use crossterm::cursor::SetCursorStyle;
+
+use crate::text::{FinalNewline, TAB_WIDTH};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    Relative,
+    // The absolute number on the cursor's line and relative ones elsewhere
+    Hybrid,
+}
+
+impl LineNumbers {
+    pub fn next(&self) -> Self {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Hybrid,
+            LineNumbers::Hybrid => LineNumbers::Off,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            LineNumbers::Off => "off",
+            LineNumbers::Absolute => "absolute",
+            LineNumbers::Relative => "relative",
+            LineNumbers::Hybrid => "hybrid",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum HeaderPath {
+    // Just the file's name
+    Name,
+    // Relative to the current directory, or as it was entered for files outside it
+    Relative,
+    Absolute,
+}
+
+impl HeaderPath {
+    pub fn next(&self) -> Self {
+        match self {
+            HeaderPath::Name => HeaderPath::Relative,
+            HeaderPath::Relative => HeaderPath::Absolute,
+            HeaderPath::Absolute => HeaderPath::Name,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            HeaderPath::Name => "name",
+            HeaderPath::Relative => "relative",
+            HeaderPath::Absolute => "absolute",
+        }
+    }
+
+    /// How a file's path is shown, falling back to the path as entered when it can't be resolved, as for unsaved files
+    pub fn format(&self, file_name: &str) -> String {
+        let path = Path::new(file_name);
+        let absolute = fs::canonicalize(path);
+        let shown = match self {
+            HeaderPath::Name => path.file_name().map(Path::new),
+            HeaderPath::Relative => {
+                let current_dir = env::current_dir().and_then(fs::canonicalize);
+                match (&absolute, &current_dir) {
+                    (Ok(absolute), Ok(current_dir)) => absolute.strip_prefix(current_dir).ok(),
+                    _ => None,
+                }
+            }
+            HeaderPath::Absolute => absolute.as_deref().ok(),
+        };
+        shown.map_or(file_name.to_string(), |shown| shown.to_string_lossy().into_owned())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CursorShape {
+    // Whatever the terminal uses unless told otherwise
+    Default,
+    Block,
+    Bar,
+    Underline,
+}
+
+impl CursorShape {
+    pub fn style(&self) -> SetCursorStyle {
+        match self {
+            CursorShape::Default => SetCursorStyle::DefaultUserShape,
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+            CursorShape::Underline => SetCursorStyle::SteadyUnderScore,
+        }
+    }
+}
+
+/// How a file is indented: the width tabs are drawn at, the columns an indent spans, and whether it's made of tabs
+#[derive(Clone, Copy)]
+pub struct Indentation {
+    pub tab_width: usize,
+    pub indent_size: usize,
+    pub hard_tabs: bool,
+}
+
+// Settings for one file type, each falling back to the global one when it isn't given
+#[derive(Default)]
+struct FileTypeIndentation {
+    tab_width: Option<usize>,
+    indent_size: Option<usize>,
+    hard_tabs: Option<bool>,
+}
+
+pub struct Config {
+    pub rulers: Vec<usize>,
+    pub highlight_overflow: bool,
+    pub highlight_word: bool,
+    pub show_line_endings: bool,
+    pub prose: bool,
+    pub focus_wrap: bool,
+    pub auto_pairs: bool,
+    pub auto_indent: bool,
+    pub backspace_outdents: bool,
+    pub syntax_highlighting: bool,
+    pub large_file_mb: u64,
+    pub poll_timeout_ms: u64,
+    pub tab_width: usize,
+    pub indent_size: usize,
+    pub hard_tabs: bool,
+    file_types: HashMap<String, FileTypeIndentation>,
+    pub status_format: String,
+    pub backup: bool,
+    pub backup_count: usize,
+    pub backup_dir: Option<PathBuf>,
+    pub line_numbers: LineNumbers,
+    pub header_path: HeaderPath,
+    pub cursor_shape: CursorShape,
+    pub trim_trailing_blank_lines: bool,
+    pub lint_on_save: bool,
+    pub bell: bool,
+    pub confirm_quit: bool,
+    pub search_wraps: bool,
+    pub build_command: String,
+    pub final_newline: FinalNewline,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self{rulers: vec![80], highlight_overflow: false, highlight_word: true, show_line_endings: false, prose: false, focus_wrap: false, auto_pairs: true, auto_indent: false, backspace_outdents: false, syntax_highlighting: true, large_file_mb: 64, poll_timeout_ms: 500, tab_width: TAB_WIDTH, indent_size: TAB_WIDTH, hard_tabs: false, file_types: HashMap::new(), status_format: String::from("Cursor: {col}, {line} -- {lines} lines{flags}"), backup: false, backup_count: 0, backup_dir: None, line_numbers: LineNumbers::Off, header_path: HeaderPath::Relative, cursor_shape: CursorShape::Default, trim_trailing_blank_lines: false, lint_on_save: false, bell: false, confirm_quit: true, search_wraps: true, build_command: String::from("cargo check"), final_newline: FinalNewline::Preserve}
+    }
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads `$XDG_CONFIG_HOME/text_editor/config` (or `~/.config/text_editor/config`), falling back to the defaults
+    pub fn load() -> Self {
+        let mut config = Config::new();
+        if let Some(contents) = Config::path().and_then(|path| fs::read_to_string(path).ok()) {
+            config.parse(&contents);
+        }
+        config
+    }
+
+    fn path() -> Option<PathBuf> {
+        let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("text_editor").join("config"))
+    }
+
+    /// The indentation for a file, from the settings for its extension (or its name, for files like `Makefile`)
+    pub fn indentation(&self, file_name: Option<&str>) -> Indentation {
+        let file_type = file_name.map(Path::new).and_then(|path| path.extension().or(path.file_name())).and_then(|file_type| file_type.to_str());
+        let settings = file_type.and_then(|file_type| self.file_types.get(file_type));
+        Indentation{
+            tab_width: settings.and_then(|settings| settings.tab_width).unwrap_or(self.tab_width),
+            indent_size: settings.and_then(|settings| settings.indent_size).unwrap_or(self.indent_size),
+            hard_tabs: settings.and_then(|settings| settings.hard_tabs).unwrap_or(self.hard_tabs),
+        }
+    }
+
+    fn parse(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((key, value)) = line.split_once('=') {
+                self.set(key.trim(), value.trim());
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        // Keys like `yaml.indent_size` only apply to one file type
+        if let Some((file_type, key)) = key.split_once('.') {
+            let settings = self.file_types.entry(file_type.to_string()).or_default();
+            match key {
+                "tab_width" => settings.tab_width = parse_width(value),
+                "indent_size" => settings.indent_size = parse_width(value),
+                "hard_tabs" => settings.hard_tabs = Some(value == "true"),
+                _ => {}
+            }
+            return;
+        }
+        match key {
+            "rulers" => self.rulers = value.split(',').filter_map(|column| column.trim().parse().ok()).collect(),
+            "highlight_overflow" => self.highlight_overflow = value == "true",
+            "prose" => self.prose = value == "true",
+            "focus_wrap" => self.focus_wrap = value == "true",
+            "auto_pairs" => self.auto_pairs = value == "true",
+            "auto_indent" => self.auto_indent = value == "true",
+            "backspace_outdents" => self.backspace_outdents = value == "true",
+            "highlight_word" => self.highlight_word = value == "true",
+            "show_line_endings" => self.show_line_endings = value == "true",
+            "syntax_highlighting" => self.syntax_highlighting = value == "true",
+            "tab_width" => {
+                if let Some(tab_width) = parse_width(value) {
+                    self.tab_width = tab_width;
+                }
+            }
+            "indent_size" => {
+                if let Some(indent_size) = parse_width(value) {
+                    self.indent_size = indent_size;
+                }
+            }
+            "hard_tabs" => self.hard_tabs = value == "true",
+            "backup" => self.backup = value == "true",
+            "backup_count" => {
+                if let Ok(backup_count) = value.parse() {
+                    self.backup_count = backup_count;
+                }
+            }
+            "backup_dir" => self.backup_dir = Some(PathBuf::from(value)),
+            "line_numbers" => {
+                self.line_numbers = match value {
+                    "absolute" => LineNumbers::Absolute,
+                    "relative" => LineNumbers::Relative,
+                    "hybrid" => LineNumbers::Hybrid,
+                    _ => LineNumbers::Off,
+                }
+            }
+            "header_path" => {
+                self.header_path = match value {
+                    "name" => HeaderPath::Name,
+                    "absolute" => HeaderPath::Absolute,
+                    _ => HeaderPath::Relative,
+                }
+            }
+            "cursor_shape" => {
+                self.cursor_shape = match value {
+                    "block" => CursorShape::Block,
+                    "bar" => CursorShape::Bar,
+                    "underline" => CursorShape::Underline,
+                    _ => CursorShape::Default,
+                }
+            }
+            "final_newline" => {
+                self.final_newline = match value {
+                    "always" => FinalNewline::Always,
+                    "never" => FinalNewline::Never,
+                    _ => FinalNewline::Preserve,
+                }
+            }
+            "trim_trailing_blank_lines" => self.trim_trailing_blank_lines = value == "true",
+            "lint_on_save" => self.lint_on_save = value == "true",
+            "bell" => self.bell = value == "true",
+            "confirm_quit" => self.confirm_quit = value == "true",
+            "search_wraps" => self.search_wraps = value == "true",
+            "build_command" => self.build_command = value.to_string(),
+            "status_format" => self.status_format = value.to_string(),
+            "large_file_mb" => {
+                if let Ok(size) = value.parse() {
+                    self.large_file_mb = size;
+                }
+            }
+            "poll_timeout_ms" => {
+                if let Ok(timeout) = value.parse() {
+                    self.poll_timeout_ms = timeout;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// A tab width or indent size, which has to be at least one column, as both are divided by
+fn parse_width(value: &str) -> Option<usize> {
+    value.parse().ok().filter(|&width| width > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::{Cursor, Text};
+
+    #[test]
+    fn zero_widths_are_ignored() {
+        let mut config = Config::new();
+        config.parse("tab_width = 0\nindent_size = 0\nrs.tab_width = 0\nrs.indent_size = 0");
+        let indentation = config.indentation(Some("main.rs"));
+        assert_eq!((indentation.tab_width, indentation.indent_size), (TAB_WIDTH, TAB_WIDTH));
+    }
+
+    #[test]
+    fn tab_width_and_indent_size_are_independent() {
+        let mut config = Config::new();
+        config.parse("tab_width = 8\nindent_size = 2");
+        let indentation = config.indentation(None);
+        assert_eq!((indentation.tab_width, indentation.indent_size), (8, 2));
+
+        let mut text = Text::new();
+        text.set_text("\tx\n\n");
+        text.set_indentation(indentation);
+        // Tab characters are drawn 8 wide, while the Tab key indents by 2
+        assert_eq!(text.display_column(0, 1), 8);
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(0, 1);
+        text.insert_char('\t', &mut cursor);
+        assert_eq!(text.get_line(1), "  ");
+        assert_eq!(text.display_column(0, 1), 8);
+
+        config.parse("rs.indent_size = 3");
+        let indentation = config.indentation(Some("main.rs"));
+        assert_eq!((indentation.tab_width, indentation.indent_size), (8, 3));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,82 @@
+use std::cmp::max;
+
+use crossterm::style::Color;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DiffTag {
+    Equal,
+    Added,
+    Removed,
+    Changed,
+}
+
+impl DiffTag {
+    pub fn colour(&self) -> Color {
+        match self {
+            DiffTag::Added => Color::DarkGreen,
+            DiffTag::Removed => Color::DarkRed,
+            DiffTag::Changed => Color::DarkBlue,
+            DiffTag::Equal => Color::Reset,
+        }
+    }
+}
+
+/// Tags each line of `old` and `new` using the longest common subsequence of their lines
+pub fn diff_lines(old: &[&str], new: &[&str]) -> (Vec<DiffTag>, Vec<DiffTag>) {
+    let mut old_tags = vec![DiffTag::Equal; old.len()];
+    let mut new_tags = vec![DiffTag::Equal; new.len()];
+
+    // Lines shared at either end are always equal, so keep them out of the table
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let (n, m) = (old_middle.len(), new_middle.len());
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if old_middle[i] == new_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                max(lengths[i + 1][j], lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && old_middle[i] == new_middle[j] {
+            tag_run(&mut old_tags, &mut new_tags, &mut removed, &mut added);
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            added.push(prefix + j);
+            j += 1;
+        } else {
+            removed.push(prefix + i);
+            i += 1;
+        }
+    }
+    tag_run(&mut old_tags, &mut new_tags, &mut removed, &mut added);
+    (old_tags, new_tags)
+}
+
+fn tag_run(old_tags: &mut [DiffTag], new_tags: &mut [DiffTag], removed: &mut Vec<usize>, added: &mut Vec<usize>) {
+    // Removals and additions at the same point pair up as changed lines
+    for k in 0..max(removed.len(), added.len()) {
+        match (removed.get(k), added.get(k)) {
+            (Some(&old), Some(&new)) => {
+                old_tags[old] = DiffTag::Changed;
+                new_tags[new] = DiffTag::Changed;
+            }
+            (Some(&old), None) => old_tags[old] = DiffTag::Removed,
+            (None, Some(&new)) => new_tags[new] = DiffTag::Added,
+            (None, None) => {}
+        }
+    }
+    removed.clear();
+    added.clear();
+}
\ No newline at end of file
@@ -0,0 +1,2418 @@
+use std::{cmp::min, collections::VecDeque, fmt::{self, Display}, io::{self, stdout, Read, Write, Stdout}, ops::Range, path::{Path, PathBuf}, sync::mpsc::{Receiver, TryRecvError}, time::{Duration, Instant}, env, fs};
+use crossterm::{cursor, event::{self, Event, KeyEvent, KeyCode, KeyModifiers, KeyEventKind, MouseButton, MouseEvent, MouseEventKind}, execute, queue, style::{self, Attribute, Color, SetBackgroundColor, SetForegroundColor}, terminal::{self, ClearType}};
+
+use crate::{backup::Backups, build::{self, Diagnostic}, calc, text::{self, Text, Cursor, SearchData, LineChange, Navigable, RustSyntax}, hex::HexView, lazy::LazyView, loader::{self, LoadProgress}, diff::{self, DiffTag}, config::{Config, Indentation, LineNumbers}, editorconfig::EditorConfig, lint, list::ListView, search, session::{Layout, Session}};
+
+macro_rules! prompt {
+    ($editor:expr,$message:expr,$default:expr $(, $callback:expr)?) => {{
+        let editor: &mut Editor<W> = $editor;
+        let message: &str = $message;
+        let mut input: String = $default;
+        // Where the input cursor is, in characters, starting after the default
+        let mut position = input.chars().count();
+        // A callback can return a note to show after the input until the next key
+        let mut note: Option<String> = None;
+        loop {
+            match &note {
+                Some(note) => editor.set_status_message(Some(format!("{} {} -- {}", message, input, note))),
+                None => editor.set_status_message(Some(format!("{} {}", message, input))),
+            }
+            editor.prompt_cursor = Some((message.chars().count() + 1 + position) as u16);
+            editor.refresh_screen()?;
+            let event = editor.read_key()?;
+            let index = input.char_indices().nth(position).map_or(input.len(), |(index, _)| index);
+            match event {
+                KeyEvent{code: KeyCode::Esc, ..} => {
+                    input.clear();
+                    break;
+                },
+                KeyEvent{code: KeyCode::Enter, ..} => break,
+                KeyEvent{code: KeyCode::Char(c), kind: KeyEventKind::Press, ..} => {
+                    input.insert(index, c);
+                    position += 1;
+                }
+                KeyEvent{code: KeyCode::Backspace, kind: KeyEventKind::Press, ..} => {
+                    if let Some(c) = input[..index].chars().next_back() {
+                        input.remove(index - c.len_utf8());
+                        position -= 1;
+                    }
+                }
+                KeyEvent{code: KeyCode::Delete, kind: KeyEventKind::Press, ..} if index < input.len() => {
+                    input.remove(index);
+                }
+                KeyEvent{code: KeyCode::Left, kind: KeyEventKind::Press, ..} => position = position.saturating_sub(1),
+                KeyEvent{code: KeyCode::Right, kind: KeyEventKind::Press, ..} => position = min(position + 1, input.chars().count()),
+                KeyEvent{code: KeyCode::Home, kind: KeyEventKind::Press, ..} => position = 0,
+                KeyEvent{code: KeyCode::End, kind: KeyEventKind::Press, ..} => position = input.chars().count(),
+                _ => {}
+            }
+            note = None;
+            if let KeyEvent{code: _key_code, kind: KeyEventKind::Press, ..} = event {
+                $(note = $callback(editor, &input, _key_code);)?   
+            }
+        }
+        editor.prompt_cursor = None;
+        editor.set_status_message(None);
+        if input.len() > 0 {
+            Some(input)
+        }else {
+            None
+        }
+    }};
+}
+
+struct TextField {
+    size: (u16, u16),
+    text: Text,
+    dirty: bool,
+    cursor: Cursor,
+    search_data: SearchData,
+    diff_tags: Vec<DiffTag>,
+    // The text's revision when the diff tags were worked out
+    diff_revision: Option<u64>,
+    read_only: bool,
+    // Whether spaces and tabs are trimmed from the ends of lines when saving
+    trim_whitespace: bool,
+    // Where a selection was started, with the cursor at its other end
+    selection: Option<(usize, usize)>,
+}
+
+impl TextField {
+    fn new(size: (u16, u16)) -> Self {
+        Self{
+            size: size, 
+            text: Text::new(), 
+            dirty: true, 
+            cursor: Cursor::new(size.clone()), 
+            search_data: SearchData::new(),
+            diff_tags: Vec::new(),
+            diff_revision: None,
+            read_only: false,
+            trim_whitespace: false,
+            selection: None,
+        }
+    }
+
+    fn load(&mut self, file_name: &String, file_contents: std::io::Result<String>, indentation: Indentation) {
+        self.cursor.set_position(0, 0);
+        self.dirty = false;
+        self.read_only = !is_writable(file_name);
+        self.text.set_syntax(text::syntax_for_file(file_name));
+        self.text.set_indentation(indentation);
+        self.text.load(file_contents);
+        self.cursor.clamp_to(&self.text);
+    }
+
+    fn reset(&mut self) {
+        self.cursor.set_position(0, 0);
+        self.dirty = false;
+        self.read_only = false;
+        self.text.reset();
+        self.cursor.clamp_to(&self.text);
+    }
+
+    /// Replaces the text, keeping the cursor where it was if that's still in the text
+    fn set_contents(&mut self, contents: String, dirty: bool) {
+        self.dirty = dirty;
+        self.text.load(Ok(contents));
+        self.cursor.clamp_to(&self.text);
+    }
+
+    fn save(&mut self, file_name: &String) -> std::io::Result<()>{
+        self.text.save(file_name)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    fn trim_trailing_whitespace(&mut self) {
+        if self.text.trim_trailing_whitespace() {
+            self.dirty = true;
+            self.clamp_cursor();
+        }
+    }
+
+    fn trim_trailing_blank_lines(&mut self) {
+        if self.text.trim_trailing_blank_lines() {
+            self.dirty = true;
+            // Only move the cursor if its line was removed
+            if self.cursor.get_position().1 >= self.text.len() {
+                self.clamp_cursor();
+            }
+        }
+    }
+
+    /// The line and first character drawn on each row of a pane. With `wrap`, the cursor's line is wrapped across as many
+    /// rows as it needs, and the rest are drawn from their start
+    fn layout(&self, view: &Cursor, wrap: bool) -> Vec<(usize, usize)> {
+        let (x_offset, y_offset) = view.get_offset();
+        let x_offset = if wrap {0} else {x_offset};
+        let (width, height) = (view.get_size().0 as usize, view.get_size().1 as usize);
+        let (x, cursor_y) = self.cursor.get_position();
+        let mut rows = Vec::with_capacity(height);
+        let mut cursor_row = 0;
+        let mut line_index = y_offset;
+        while rows.len() < height || rows.len() <= cursor_row {
+            if wrap && line_index == cursor_y {
+                let line_width = self.text.display_column(line_index, self.text.line_len(line_index));
+                let cursor_column = self.text.display_column(line_index, x);
+                cursor_row = rows.len() + cursor_column / width;
+                for chunk in 0..line_width.div_ceil(width).max(cursor_column / width + 1) {
+                    rows.push((line_index, Navigable::position_at_column(&self.text, line_index, chunk * width)));
+                }
+            } else {
+                rows.push((line_index, x_offset));
+            }
+            line_index += 1;
+        }
+        // A wrapped line running off the bottom scrolls up to keep the cursor in view
+        rows.drain(..(cursor_row + 1).saturating_sub(height));
+        rows.truncate(height);
+        rows
+    }
+
+    /// Draws part of a line, from its character `start`, on row `y` of a pane
+    fn print_line(&self, w: &mut impl Write, view: &Cursor, origin: (u16, u16), y: u16, (line_index, start): (usize, usize), config: &Config) -> std::io::Result<()> {
+        let width = view.get_size().0 as usize;
+        let background = self.diff_tags.get(line_index).map_or(Color::Reset, DiffTag::colour);
+        let change = self.text.get_change(line_index);
+        if change != LineChange::Unchanged {
+            queue!(w, cursor::MoveTo(origin.0 - 1, origin.1 + y), SetForegroundColor(change.colour()), style::Print(change.marker()), SetForegroundColor(Color::Reset))?;
+        }
+        queue!(w, cursor::MoveTo(origin.0, origin.1 + y), SetBackgroundColor(background))?;
+        let overflow_column = if config.highlight_overflow {config.rulers.iter().min().copied()} else {None};
+        let (cursor_x, cursor_y) = self.cursor.get_position();
+        let mut marks = Vec::new();
+        if let Some(selected) = self.selected_range(line_index) {
+            marks.push((selected, Attribute::Reverse));
+        }
+        if let Some(word) = self.text.word_at(cursor_y, cursor_x).filter(|_| config.highlight_word) {
+            marks.extend(self.text.word_occurrences(line_index, word).into_iter().map(|range| (range, Attribute::Underlined)));
+        }
+        let mut printed = self.text.print_line(w, line_index, start, width, overflow_column, &marks)?;
+        let start_column = self.text.display_column(line_index, start);
+        // A line's ending is shown just after it, once the end of the line is on screen
+        let end_column = self.text.display_column(line_index, self.text.line_len(line_index));
+        if let Some(ending) = self.text.line_ending(line_index).filter(|_| config.show_line_endings && start_column + printed == end_column) {
+            let glyph = if ending == "\r\n" {"␍␊"} else {"␊"};
+            if printed + glyph.chars().count() <= width {
+                queue!(w, SetForegroundColor(Color::DarkGrey), style::Print(glyph), SetForegroundColor(Color::Reset))?;
+                printed += glyph.chars().count();
+            }
+        }
+        if background != Color::Reset {
+            queue!(w, style::Print(" ".repeat(width - printed)), SetBackgroundColor(Color::Reset))?;
+        }
+        for &ruler in &config.rulers {
+            if ruler >= start_column + printed && ruler < start_column + width {
+                let ruler_x = origin.0 + (ruler - start_column) as u16;
+                queue!(w, cursor::MoveTo(ruler_x, origin.1 + y), SetForegroundColor(Color::DarkGrey), style::Print('│'), SetForegroundColor(Color::Reset))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The part of a line between the selection's anchor and the cursor
+    fn selected_range(&self, index: usize) -> Option<Range<usize>> {
+        let (anchor_x, anchor_y) = self.selection?;
+        let (x, y) = self.cursor.get_position();
+        let ((start_x, start_y), (end_x, end_y)) = if (anchor_y, anchor_x) <= (y, x) {((anchor_x, anchor_y), (x, y))} else {((x, y), (anchor_x, anchor_y))};
+        if index < start_y || index > end_y {
+            return None;
+        }
+        let start = if index == start_y {start_x} else {0};
+        let end = if index == end_y {end_x} else {self.text.line_len(index)};
+        Some(start..end)
+    }
+
+    /// Places the cursor at a row and column of the pane, selecting the word there on a double-click and the line on a triple-click
+    fn click(&mut self, column: usize, row: usize, clicks: u8) {
+        let (x_offset, y_offset) = self.cursor.get_offset();
+        let y = min(row + y_offset, self.text.len() - 1);
+        let x = Navigable::position_at_column(&self.text, y, self.text.display_column(y, x_offset) + column);
+        let (start, end) = match clicks {
+            2 => self.text.word_bounds(y, x).map_or((x, x), |bounds| (bounds.start, bounds.end)),
+            3 => (0, self.text.line_len(y)),
+            _ => (x, x),
+        };
+        self.selection = if start == end {None} else {Some((start, y))};
+        self.cursor.set_position(end, y);
+        self.cursor.change_offset(&self.text);
+    }
+
+    fn get_cursor_position(&self, origin: (u16, u16), wrap: bool) -> (u16, u16) {
+        let (x, y) = self.cursor.get_position();
+        let cursor_column = self.text.display_column(y, x);
+        // The cursor is on the last row of its line that starts at or before it
+        let rows = self.layout(&self.cursor, wrap);
+        let (row, start) = rows.iter().enumerate().rev()
+            .find(|(_, &(line_index, start))| line_index == y && self.text.display_column(y, start) <= cursor_column)
+            .map_or((0, 0), |(row, &(_, start))| (row, start));
+        let column = cursor_column.saturating_sub(self.text.display_column(y, start));
+        (column as u16 + origin.0, row as u16 + origin.1)
+    }
+
+    /// Whether the cursor's line is on one of the pane's rows, which it may not be after scrolling with the mouse wheel
+    fn cursor_in_view(&self, wrap: bool) -> bool {
+        let y = self.cursor.get_position().1;
+        self.layout(&self.cursor, wrap).iter().any(|&(line_index, _)| line_index == y)
+    }
+
+    /// Moves the cursor and the view up or down by a page, so the cursor stays on the same row
+    fn page(&mut self, direction: KeyCode) {
+        let height = self.cursor.get_size().1 as isize;
+        let y = self.cursor.get_position().1;
+        for _ in 0..height {
+            self.cursor.move_cursor(&self.text, direction);
+        }
+        let moved = self.cursor.get_position().1 as isize - y as isize;
+        self.cursor.scroll(moved, self.text.len());
+        self.cursor.change_offset(&self.text);
+    }
+
+    fn clamp_cursor(&mut self) {
+        self.cursor.clamp_to(&self.text);
+    }
+
+    fn move_cursor(&mut self, direction: KeyCode) {
+        let cursor = &mut self.cursor;
+        cursor.move_cursor(&self.text, direction);
+        self.cursor.change_offset(&self.text);
+    }
+
+    /// Updates the search for a key pressed in the find prompt, returning whether moving between results went past the last (or first)
+    fn find_phrase(&mut self, phrase: &String, key_code: KeyCode) -> bool {
+        let (position, at_end) = match key_code {
+            KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Delete => {
+                (self.search_data.find_results(phrase, &mut self.text), false)
+            },
+            KeyCode::Down => {
+                self.search_data.get_next().map_or((None, false), |(position, at_end)| (Some(position), at_end))
+            },
+            KeyCode::Up => {
+                self.search_data.get_previous().map_or((None, false), |(position, at_end)| (Some(position), at_end))
+            }
+            _ => (None, false)
+        };
+        if let Some((x, y)) = position {
+            self.cursor.set_position(x, y);
+            self.cursor.change_offset(&self.text);
+        }
+        at_end
+    }
+
+    /// Jumps to the next or previous place the word under the cursor appears, returning the word and whether there were
+    /// no more in that direction
+    fn find_word(&mut self, forward: bool) -> Option<(String, bool)> {
+        let (x, y) = self.cursor.get_position();
+        let word = self.text.word_at(y, x)?.to_string();
+        self.search_data.find_word(&word, &mut self.text);
+        while self.search_data.is_searching() {
+            self.search_data.continue_search(&mut self.text);
+        }
+        self.search_data.select_at((x, y));
+        let ((x, y), wrapped) = if forward {self.search_data.get_next()?} else {self.search_data.get_previous()?};
+        self.cursor.set_position(x, y);
+        self.cursor.change_offset(&self.text);
+        Some((word, wrapped))
+    }
+
+    fn continue_find(&mut self) {
+        if let Some((x, y)) = self.search_data.continue_search(&mut self.text) {
+            self.cursor.set_position(x, y);
+            self.cursor.change_offset(&self.text);
+        }
+    }
+
+    fn end_find(&mut self) {
+        self.search_data.find_results(&String::from(""), &mut self.text);
+    }
+
+    fn insert_char(&mut self, c: char) {
+        self.text.insert_char(c, &mut self.cursor);
+        self.cursor.change_offset(&self.text);
+        self.dirty = true;
+    }
+
+    fn move_to(&mut self, position: (usize, usize)) {
+        let (x, y) = self.text.clamp_position(position);
+        self.cursor.set_position(x, y);
+        self.cursor.change_offset(&self.text);
+    }
+
+    fn increment_number(&mut self, delta: i64) -> bool {
+        let changed = self.text.increment_number(&mut self.cursor, delta);
+        if changed {
+            self.cursor.change_offset(&self.text);
+            self.dirty = true;
+        }
+        changed
+    }
+
+    fn insert_literal(&mut self, c: char) {
+        self.text.insert_literal(c, &mut self.cursor);
+        self.cursor.change_offset(&self.text);
+        self.dirty = true;
+    }
+
+    fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(s, &mut self.cursor);
+        self.cursor.change_offset(&self.text);
+        self.dirty = true;
+    }
+
+    /// Removes one level of indentation from the cursor's line, or from every line the selection touches, returning
+    /// whether any were indented
+    fn dedent(&mut self) -> bool {
+        let (x, y) = self.cursor.get_position();
+        let (first, last) = self.selection.map_or((y, y), |(_, anchor_y)| (min(y, anchor_y), y.max(anchor_y)));
+        let mut changed = false;
+        for index in first..=last {
+            let removed = self.text.dedent_line(index);
+            if removed == 0 {
+                continue;
+            }
+            changed = true;
+            if index == y {
+                self.cursor.set_position(x.saturating_sub(removed), y);
+            }
+            if let Some((anchor_x, anchor_y)) = self.selection.filter(|&(_, anchor_y)| anchor_y == index) {
+                self.selection = Some((anchor_x.saturating_sub(removed), anchor_y));
+            }
+        }
+        if changed {
+            self.cursor.change_offset(&self.text);
+            self.dirty = true;
+        }
+        changed
+    }
+
+    fn new_line(&mut self) {
+        self.text.new_line(&mut self.cursor);
+        self.cursor.change_offset(&self.text);
+        self.dirty = true;
+    }
+
+    fn open_line(&mut self, above: bool) {
+        self.text.open_line(&mut self.cursor, above);
+        self.cursor.change_offset(&self.text);
+        self.dirty = true;
+    }
+
+    fn delete_char(&mut self) {
+        self.text.delete_char(&mut self.cursor);
+        self.cursor.change_offset(&self.text);
+        self.dirty = true;
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum SplitDirection {
+    Horizontal,
+    Vertical,
+}
+
+enum Pane {
+    View(Cursor),
+    Buffer(Box<TextField>, Option<String>),
+}
+
+struct Split {
+    direction: SplitDirection,
+    other: Pane,
+    focus_second: bool,
+    diff: bool,
+}
+
+// Where `--print-position-on-exit` writes the cursor's final position
+enum PositionOutput {
+    Stderr,
+    File(String),
+}
+
+/// Where the editor was left, for a script that started it to pick up from, written as a line of JSON
+struct ExitPosition<'a> {
+    file_name: Option<&'a str>,
+    // Counted from 1, like `+<line>`
+    line: usize,
+    column: usize,
+    lines: usize,
+    dirty: bool,
+}
+
+impl fmt::Display for ExitPosition<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let file_name = self.file_name.map_or(String::from("null"), json_string);
+        writeln!(f, "{{\"file\": {}, \"line\": {}, \"column\": {}, \"lines\": {}, \"dirty\": {}}}", file_name, self.line, self.column, self.lines, self.dirty)
+    }
+}
+
+// A JSON string literal, escaping quotes, backslashes and control characters
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+type IdleHook<W> = Box<dyn FnMut(&mut Editor<W>)>;
+
+/// The whole editor: its buffers, panes and prompts, drawn to `w`.
+/// Key events come from the terminal, or from a script when the editor is headless.
+pub struct Editor<W: Write> {
+    running: bool,
+    win_size: (u16, u16),
+    w: W,
+    scripted_keys: Option<VecDeque<KeyEvent>>,
+    file_name: Option<String>,
+    text_field: TextField,
+    hex_view: Option<HexView>,
+    lazy_view: Option<LazyView>,
+    split: Option<Split>,
+    status_message: Option<String>,
+    search_phrase: String,
+    pending_count: Option<usize>,
+    // When and where the mouse was last clicked, and how many clicks in a row that made
+    last_click: Option<(Instant, (u16, u16), u8)>,
+    // Whether the status message is flashed, as it's about something that couldn't be done
+    alert: bool,
+    // Rung when the screen is next drawn
+    ring_bell: bool,
+    // Run each time waiting for a key times out
+    idle_hook: Option<IdleHook<W>>,
+    // The column of the status line the cursor is drawn at while a prompt is open
+    prompt_cursor: Option<u16>,
+    position_output: Option<PositionOutput>,
+    // The errors and warnings from the last build, and which F8 goes to next
+    diagnostics: Vec<Diagnostic>,
+    next_diagnostic: usize,
+    config: Config,
+    // Puts the terminal back when the editor goes, so it's dropped last
+    terminal: Option<TerminalGuard>,
+}
+
+/// Sets the terminal up for the editor and puts it back when dropped, however the editor exits: normally, through an
+/// error or in a panic
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        // Anything after this that fails is undone by dropping the guard
+        let guard = Self;
+        execute!(stdout(), terminal::EnterAlternateScreen, event::EnableMouseCapture, event::EnableBracketedPaste)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        // There's nowhere to report a failure from here, so each step is tried regardless. The shell gets its own
+        // cursor back rather than the configured one
+        let _ = execute!(stdout(), style::ResetColor, cursor::SetCursorStyle::DefaultUserShape, cursor::Show, event::DisableBracketedPaste, event::DisableMouseCapture, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// Below this many columns and rows there's no room to edit, so only a message is drawn
+const MIN_WIN_SIZE: (u16, u16) = (20, 6);
+
+// The text area for a window, laid out as if the window were at least the minimum size so smaller ones can't underflow
+fn text_area_size(win_size: (u16, u16)) -> (u16, u16) {
+    (win_size.0.max(MIN_WIN_SIZE.0) - 2, win_size.1.max(MIN_WIN_SIZE.1) - 3)
+}
+
+// The longest gap between clicks that still counts them as a double- or triple-click
+const MULTI_CLICK_TIME: Duration = Duration::from_millis(400);
+// How many lines one notch of the mouse wheel scrolls
+const SCROLL_LINES: isize = 3;
+
+// Shown by the help screen, in the order it lists them
+const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("F1", "Show this help"),
+    ("Ctrl-S", "Save"),
+    ("Ctrl-L", "Load a file"),
+    ("Ctrl-C", "Quit"),
+    ("Ctrl-F", "Find (Up/Down for the previous/next result)"),
+    ("Left/Right, Home/End", "Move within what's typed at a prompt"),
+    ("Alt-F", "Find in files"),
+    ("Alt-G", "Jump to a percentage of the way through the file"),
+    ("Alt-* / Alt-#", "Jump to the next/previous place the word under the cursor appears"),
+    ("F12", "Jump to where the word under the cursor is defined (again for earlier definitions)"),
+    ("F8", "Go to the next error or warning from the last build"),
+    ("Ctrl-P", "Run a command (stats, syntax, grep, build, paste, readonly, insert-file, insert-unicode, calc, session-save, write, reverse, outline, lint, retab-spaces, retab-tabs, prose, word-highlight, line-endings, line-numbers, header-path)"),
+    ("Ctrl-W", "Window commands: [s] split, [v] vertical split, [w] switch pane, [q] close pane"),
+    ("Ctrl-B", "Toggle the hex view"),
+    ("Alt-Z", "Toggle focus wrap, which wraps the cursor's line across as many rows as it needs"),
+    ("Ctrl-A / Ctrl-X", "Add one to or subtract one from the number under the cursor (or the count typed with Alt)"),
+    ("Ctrl-V", "Insert the next key exactly as typed (a literal tab, or Esc and Ctrl keys as control characters)"),
+    ("Arrow keys", "Move the cursor"),
+    ("Mouse", "Click to place the cursor, double-click to select a word and triple-click to select a line"),
+    ("Mouse wheel", "Scroll the view, leaving the cursor and selection where they are"),
+    ("PageUp/PageDown", "Move the cursor up/down a page, extending any selection"),
+    ("Alt-0..9", "Type a count to repeat the next arrow key by"),
+    ("Ctrl-Up/Down", "Move to the previous/next paragraph (in prose mode or plain text)"),
+    ("Ctrl-Left/Right", "Move to the previous/next sentence (in prose mode or plain text)"),
+    ("Alt-Up/Down", "Move to the previous/next line indented the same, within the same block"),
+    ("Alt-Left", "Move to the line that opens the block, the nearest one above that's indented less"),
+    ("Tab", "Indent to the next indent stop"),
+    ("Shift-Tab", "Remove one level of indentation from the line, or from every selected line"),
+    ("Enter", "Split the line"),
+    ("Alt-Enter / Alt-Shift-Enter", "Open a new line below/above the cursor's line, without splitting it"),
+    ("Backspace", "Delete the previous character, or back to the previous indent stop"),
+];
+
+/// Parses a one-based `first-last` range of lines, or a single line number
+fn parse_line_range(range: &str) -> Option<(usize, usize)> {
+    match range.split_once('-') {
+        Some((first, last)) => Some((first.trim().parse().ok()?, last.trim().parse().ok()?)),
+        None => {
+            let line = range.trim().parse().ok()?;
+            Some((line, line))
+        }
+    }
+}
+
+// Files that don't exist yet can be created, so only a refused open counts as unwritable
+fn is_writable(file_name: &String) -> bool {
+    match fs::OpenOptions::new().write(true).open(file_name) {
+        Err(error) => error.kind() != io::ErrorKind::PermissionDenied,
+        Ok(_) => true,
+    }
+}
+
+fn check_text(file_name: &String) -> std::io::Result<()> {
+    if let Ok(mut file) = fs::File::open(file_name) {
+        let mut chunk = [0; 8192];
+        let len = file.read(&mut chunk)?;
+        if chunk[..len].contains(&0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "file appears to be binary"));
+        }
+    }
+    Ok(())
+}
+
+// Loads a file into a text field with the user's settings, and those any `.editorconfig` has for it on top. A file that
+// wasn't all UTF-8 is opened read-only, as saving it would write replacement characters over the bytes that weren't,
+// and whether it was is returned
+fn load_text_field(text_field: &mut TextField, file_name: &String, file_contents: std::io::Result<(String, bool)>, config: &Config) -> bool {
+    let (file_contents, replaced) = match file_contents {
+        Ok((contents, replaced)) => (Ok(contents), replaced),
+        Err(error) => (Err(error), false),
+    };
+    let editorconfig = EditorConfig::find(file_name);
+    text_field.load(file_name, file_contents, editorconfig.indentation(config.indentation(Some(file_name))));
+    text_field.read_only |= replaced;
+    editorconfig.apply(&mut text_field.text, config.final_newline);
+    text_field.trim_whitespace = editorconfig.trim_trailing_whitespace.unwrap_or(false);
+    if !config.syntax_highlighting {
+        text_field.text.set_syntax(None);
+    }
+    replaced
+}
+
+impl Editor<Stdout> {
+    pub fn new() -> std::io::Result<Self> {
+        Editor::on_terminal(stdout())
+    }
+}
+
+impl<W: Write> Editor<W> {
+    /// Creates an editor that reads keys from the terminal but draws into `w`, which doesn't have to be stdout
+    pub fn on_terminal(w: W) -> std::io::Result<Self> {
+        let guard = TerminalGuard::new()?;
+        let win_size = terminal::size()?;
+        let mut editor = Editor::with_writer(w, win_size, None, Config::load());
+        editor.terminal = Some(guard);
+        let args: Vec<String> = env::args().skip(1).collect();
+        editor.open_args(&args);
+        Ok(editor)
+    }
+
+    fn with_writer(w: W, win_size: (u16, u16), scripted_keys: Option<VecDeque<KeyEvent>>, config: Config) -> Self {
+        let text_field = TextField::new(text_area_size(win_size));
+        let mut editor = Self { running: true, win_size, w, scripted_keys, file_name: None, text_field: text_field, hex_view: None, lazy_view: None, split: None, status_message: None, search_phrase: String::new(), pending_count: None, last_click: None, alert: false, ring_bell: false, idle_hook: None, prompt_cursor: None, position_output: None, diagnostics: Vec::new(), next_diagnostic: 0, config, terminal: None};
+        if !editor.config.syntax_highlighting {
+            editor.text_field.text.set_syntax(None);
+        }
+        editor.text_field.text.set_indentation(editor.config.indentation(None));
+        editor.text_field.text.set_final_newline_policy(editor.config.final_newline);
+        editor.text_field.text.set_auto_pairs(editor.config.auto_pairs);
+        editor.text_field.text.set_auto_indent(editor.config.auto_indent);
+        editor.text_field.text.set_outdent_before_join(editor.config.backspace_outdents);
+        editor
+    }
+
+    /// Creates an editor that draws into `w` instead of the terminal and only reads keys passed to `push_keys`,
+    /// using the default settings rather than the user's config
+    pub fn headless(w: W, win_size: (u16, u16)) -> Self {
+        Editor::with_writer(w, win_size, Some(VecDeque::new()), Config::new())
+    }
+
+    /// Creates a headless editor with the given settings in place of the defaults
+    pub fn headless_with_config(w: W, win_size: (u16, u16), config: Config) -> Self {
+        Editor::with_writer(w, win_size, Some(VecDeque::new()), config)
+    }
+
+    /// Queues keys for a headless editor to read
+    pub fn push_keys(&mut self, keys: impl IntoIterator<Item = KeyEvent>) {
+        if let Some(scripted_keys) = &mut self.scripted_keys {
+            scripted_keys.extend(keys);
+        }
+    }
+
+    pub fn text(&self) -> &Text {
+        &self.text_field.text
+    }
+
+    /// Sets something to do each time `poll_timeout_ms` passes without a key, such as checking timers or background work.
+    /// The screen is redrawn after it runs
+    pub fn set_idle_hook(&mut self, hook: impl FnMut(&mut Editor<W>) + 'static) {
+        self.idle_hook = Some(Box::new(hook));
+    }
+
+    pub fn cursor(&self) -> &Cursor {
+        &self.text_field.cursor
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.w
+    }
+
+    pub fn into_writer(self) -> W {
+        self.w
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Opens the files named on the command line, with options such as `--readonly` allowed anywhere before a `--`.
+    /// `+N` starts on line N, and `+` on the last line
+    fn open_args(&mut self, args: &[String]) {
+        let mut files = Vec::new();
+        let mut diff = false;
+        let mut read_only = false;
+        let mut start_line = None;
+        let mut session = None;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => files.extend(args.by_ref()),
+                "--diff" => diff = true,
+                "--readonly" => read_only = true,
+                "--session" => match args.next() {
+                    Some(path) => session = Some(path),
+                    None => self.set_status_message(Some(String::from("--session needs the path of a session file"))),
+                },
+                "--print-position-on-exit" => self.position_output = Some(PositionOutput::Stderr),
+                option if option.starts_with("--print-position-on-exit=") => {
+                    self.position_output = Some(PositionOutput::File(option["--print-position-on-exit=".len()..].to_string()));
+                }
+                "+" => start_line = Some(usize::MAX),
+                line if line.starts_with('+') => match line[1..].parse::<usize>() {
+                    Ok(line) => start_line = Some(line.saturating_sub(1)),
+                    Err(_) => self.set_status_message(Some(format!("Not a line number: {}", line))),
+                },
+                option if option.starts_with("--") => {
+                    self.set_status_message(Some(format!("Unknown option: {}", option)));
+                }
+                _ => files.push(arg),
+            }
+        }
+        match files.as_slice() {
+            [] if session.is_some() => {
+                let path = session.unwrap();
+                match Session::load(path) {
+                    Ok(session) => self.restore_session(&session),
+                    Err(error) => self.set_status_message(Some(format!("Could not read session {}: {}", path, error))),
+                }
+            }
+            _ if session.is_some() => self.set_status_message(Some(String::from("--session can't be used with files to open"))),
+            [old, new] if diff => self.open_diff(old, new),
+            _ if diff => self.set_status_message(Some(String::from("--diff needs exactly two files"))),
+            [name, others @ ..] => {
+                self.file_name = Some(name.to_string());
+                if let Err(error) = self.open(name) {
+                    self.set_status_message(Some(format!("Could not open {}: {}", name, error)));
+                    self.file_name = None;
+                } else if !others.is_empty() {
+                    // Only one file can be edited at a time
+                    self.set_status_message(Some(format!("Opened {}, and ignored {} more file(s)", name, others.len())));
+                }
+            }
+            [] => {}
+        }
+        if read_only {
+            self.text_field.read_only = true;
+        }
+        if let Some(line) = start_line {
+            self.text_field.cursor.set_position(0, line);
+            self.text_field.clamp_cursor();
+        }
+    }
+
+    /// The open files and cursors, with how the window is split
+    fn session(&self) -> Session {
+        let active = (self.file_name.clone(), self.text_field.cursor.get_position());
+        let (layout, other) = match &self.split {
+            None => return Session{layout: Layout::Single, focus: 0, panes: vec![active]},
+            Some(Split{other: Pane::Buffer(text_field, file_name), ..}) => (Layout::Diff, (file_name.clone(), text_field.cursor.get_position())),
+            Some(Split{direction, other: Pane::View(cursor), ..}) => {
+                let layout = if *direction == SplitDirection::Horizontal {Layout::Horizontal} else {Layout::Vertical};
+                (layout, (self.file_name.clone(), cursor.get_position()))
+            }
+        };
+        let focus = self.active_pane();
+        let panes = if focus == 1 {vec![other, active]} else {vec![active, other]};
+        Session{layout, focus, panes}
+    }
+
+    /// Opens the files from a session and puts the cursors back, skipping files that no longer exist
+    fn restore_session(&mut self, session: &Session) {
+        let exists = |file_name: &Option<String>| file_name.as_ref().is_some_and(|name| Path::new(name).is_file());
+        match (session.layout, session.panes.as_slice()) {
+            (Layout::Diff, [(Some(old), old_cursor), (Some(new), new_cursor)]) if Path::new(old).is_file() && Path::new(new).is_file() => {
+                self.open_diff(old, new);
+                if let Some(Split{other: Pane::Buffer(other, _), ..}) = &mut self.split {
+                    other.cursor.set_position(new_cursor.0, new_cursor.1);
+                    other.clamp_cursor();
+                }
+                self.text_field.cursor.set_position(old_cursor.0, old_cursor.1);
+                self.text_field.clamp_cursor();
+            }
+            (layout, [(file_name, cursor), others @ ..]) if exists(file_name) => {
+                let name = file_name.clone().unwrap();
+                if let Err(error) = self.open(&name) {
+                    self.set_status_message(Some(format!("Could not open {}: {}", name, error)));
+                    return;
+                }
+                self.file_name = Some(name);
+                let direction = match layout {
+                    Layout::Horizontal => Some(SplitDirection::Horizontal),
+                    Layout::Vertical => Some(SplitDirection::Vertical),
+                    _ => None,
+                };
+                // Both panes of a split show the same buffer, so the second one's cursor is placed first and kept by the split
+                if let (Some(direction), [(_, other_cursor), ..]) = (direction, others) {
+                    self.text_field.cursor.set_position(other_cursor.0, other_cursor.1);
+                    self.text_field.clamp_cursor();
+                    self.split_window(direction);
+                }
+                self.text_field.cursor.set_position(cursor.0, cursor.1);
+                self.text_field.clamp_cursor();
+            }
+            _ => {
+                self.set_status_message(Some(String::from("The session's files no longer exist")));
+                return;
+            }
+        }
+        if session.focus == 1 {
+            self.switch_pane();
+        }
+    }
+
+    fn save_session(&mut self) -> std::io::Result<()> {
+        let path = match prompt!(self, "Save session to:", String::from("text_editor.session")) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        match self.session().save(&path) {
+            Ok(()) => self.set_status_message(Some(format!("Session saved -- restore it with --session {}", path))),
+            Err(error) => self.set_status_message(Some(format!("Could not save session to {}: {}", path, error))),
+        }
+        Ok(())
+    }
+
+    fn open_diff(&mut self, old: &String, new: &String) {
+        let mut other = TextField::new(self.text_field.size);
+        other.text.set_final_newline_policy(self.config.final_newline);
+        other.text.set_auto_pairs(self.config.auto_pairs);
+        other.text.set_auto_indent(self.config.auto_indent);
+        other.text.set_outdent_before_join(self.config.backspace_outdents);
+        for (text_field, name) in [(&mut self.text_field, old), (&mut other, new)] {
+            if let Err(error) = check_text(name) {
+                self.status_message = Some(format!("Could not open {}: {}", name, error));
+                return;
+            }
+            if load_text_field(text_field, name, fs::read(name).map(loader::decode), &self.config) {
+                self.status_message = Some(format!("{} isn't valid UTF-8, so it was opened read-only", name));
+            }
+        }
+        self.file_name = Some(old.clone());
+        self.split = Some(Split{direction: SplitDirection::Vertical, other: Pane::Buffer(Box::new(other), Some(new.clone())), focus_second: false, diff: true});
+        self.resize_panes();
+        self.update_diff();
+    }
+
+    fn update_diff(&mut self) {
+        if let Some(Split{other: Pane::Buffer(other, _), focus_second, diff: true, ..}) = &mut self.split {
+            // The old file always stays in the first pane
+            let other = &mut **other;
+            let (old, new) = if *focus_second {(other, &mut self.text_field)} else {(&mut self.text_field, other)};
+            // Diffing compares every line with every other, so it's only done again when one of the texts has changed
+            // since the last time, and not for keys that just move around
+            if old.diff_revision != Some(old.text.revision()) || new.diff_revision != Some(new.text.revision()) {
+                let old_lines: Vec<&str> = (0..old.text.len()).map(|i| old.text.get_line(i)).collect();
+                let new_lines: Vec<&str> = (0..new.text.len()).map(|i| new.text.get_line(i)).collect();
+                let (old_tags, new_tags) = diff::diff_lines(&old_lines, &new_lines);
+                old.diff_tags = old_tags;
+                new.diff_tags = new_tags;
+                old.diff_revision = Some(old.text.revision());
+                new.diff_revision = Some(new.text.revision());
+            }
+
+            // Scroll both panes together so matching regions stay side by side
+            let (active, other) = if *focus_second {(new, old)} else {(old, new)};
+            let (x_offset, y_offset) = active.cursor.get_offset();
+            other.cursor.set_offset(x_offset, y_offset);
+        }
+    }
+
+    fn open(&mut self, file_name: &String) -> std::io::Result<()> {
+        let file_size = fs::metadata(file_name).map_or(0, |metadata| metadata.len());
+        if file_size > self.config.large_file_mb * 1024 * 1024 {
+            let tab_width = EditorConfig::find(file_name).indentation(self.config.indentation(Some(file_name))).tab_width;
+            self.lazy_view = Some(LazyView::open(file_name, self.text_field.size, tab_width)?);
+            self.hex_view = None;
+            self.text_field.reset();
+            self.set_status_message(Some(String::from("Large file opened read-only")));
+            return Ok(());
+        }
+        match check_text(file_name) {
+            Err(error) if error.kind() == io::ErrorKind::InvalidData => {
+                self.hex_view = Some(HexView::new(fs::read(file_name)?, self.text_field.size, false));
+                self.lazy_view = None;
+                self.text_field.reset();
+                self.set_status_message(Some(String::from("Binary file opened in hex view")));
+            }
+            result => {
+                result?;
+                let file_contents = match self.read_file(file_name)? {
+                    Some(file_contents) => file_contents,
+                    None => return Err(io::Error::new(io::ErrorKind::Interrupted, "loading cancelled")),
+                };
+                let replaced = load_text_field(&mut self.text_field, file_name, file_contents, &self.config);
+                self.hex_view = None;
+                self.lazy_view = None;
+                if replaced {
+                    self.set_status_message(Some(String::from("File isn't valid UTF-8, so it was opened read-only with the invalid bytes replaced")));
+                } else if self.text_field.read_only {
+                    self.set_status_message(Some(String::from("File isn't writable, so it was opened read-only")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a file in the background, showing progress in the status bar, or None if Esc cancelled it
+    fn read_file(&mut self, file_name: &String) -> std::io::Result<Option<std::io::Result<(String, bool)>>> {
+        let receiver = loader::read_file(PathBuf::from(file_name));
+        let mut percent = 0;
+        loop {
+            loop {
+                // A headless editor waits for the whole file, as its scripted keys are meant for after it has opened
+                let progress = match &self.scripted_keys {
+                    Some(_) => receiver.recv().map_err(|_| TryRecvError::Disconnected),
+                    None => receiver.try_recv(),
+                };
+                match progress {
+                    Ok(LoadProgress::Reading(read, total)) => percent = (read * 100).checked_div(total).unwrap_or(100),
+                    Ok(LoadProgress::Done(result)) => return Ok(Some(result)),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return Ok(Some(Err(io::Error::other("loading failed")))),
+                }
+            }
+            let status_message = format!("Loading… {}% -- Esc to cancel", percent);
+            queue!(&mut self.w, cursor::MoveTo(0, self.win_size.1.saturating_sub(1)), terminal::Clear(ClearType::UntilNewLine), style::Print(status_message))?;
+            self.w.flush()?;
+            if let Some(KeyEvent{code: KeyCode::Esc, ..}) = self.poll_key(Duration::from_millis(50))? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn is_dirty(&self) -> bool {
+        match &self.hex_view {
+            Some(hex_view) => hex_view.is_dirty(),
+            None if self.lazy_view.is_some() => false,
+            None => self.text_field.is_dirty(),
+        }
+    }
+
+    // Whether either pane has changes that haven't been saved
+    fn has_unsaved_changes(&self) -> bool {
+        self.is_dirty() || matches!(&self.split, Some(Split{other: Pane::Buffer(other, _), ..}) if other.is_dirty())
+    }
+
+    fn print_header(&mut self) -> std::io::Result<()> {
+        let ver = option_env!("CARGO_PKG_VERSION").expect("Could not find version");
+        let file_name = match &self.file_name {
+            Some(name) => format!("{}{}{}", if self.is_dirty() {"*"} else {""}, self.config.header_path.format(name), if self.text_field.read_only {" [RO]"} else {""}),
+            None => String::from("Untitled")
+        };
+        let mut welcome_message = format!("{} -- Christopher's text editor -- {}", file_name, ver);
+        welcome_message.truncate(self.win_size.0 as usize);
+        queue!(&mut self.w, cursor::MoveTo(0,0), terminal::Clear(ClearType::UntilNewLine), style::Print(welcome_message))?;
+        queue!(&mut self.w, cursor::MoveTo(0,1), terminal::Clear(ClearType::UntilNewLine))
+    }
+
+    fn read_key(&mut self) -> std::io::Result<KeyEvent> {
+        loop {
+            // An unfinished search carries on between keystrokes
+            if self.text_field.search_data.is_searching() {
+                self.text_field.continue_find();
+                self.refresh_screen()?;
+                if let Some(event) = self.poll_key(Duration::ZERO)? {
+                    return Ok(event);
+                }
+                continue;
+            }
+            if let Some(event) = self.poll_key(Duration::from_millis(self.config.poll_timeout_ms))? {
+                return Ok(event);
+            }
+            self.idle()?;
+        }
+    }
+
+    // Work done between keys while the editor waits for one
+    fn idle(&mut self) -> std::io::Result<()> {
+        if let Some(mut hook) = self.idle_hook.take() {
+            hook(self);
+            // The hook may have replaced itself
+            if self.idle_hook.is_none() {
+                self.idle_hook = Some(hook);
+            }
+            self.refresh_screen()?;
+        }
+        Ok(())
+    }
+
+    fn poll_key(&mut self, timeout: Duration) -> std::io::Result<Option<KeyEvent>> {
+        if let Some(scripted_keys) = &mut self.scripted_keys {
+            // A script that runs out part way through a prompt can't be waited on
+            return match scripted_keys.pop_front() {
+                Some(event) => Ok(Some(event)),
+                None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no more scripted keys")),
+            };
+        }
+        if event::poll(timeout)? {
+            match event::read()? {
+                Event::Key(event) => return Ok(Some(event)),
+                Event::Resize(width, height) => {
+                    self.resize((width, height));
+                    self.refresh_screen()?;
+                }
+                Event::Mouse(mouse) => {
+                    self.handle_mouse(mouse);
+                    self.refresh_screen()?;
+                }
+                // Text pasted into a prompt is dropped rather than landing in the buffer behind it
+                Event::Paste(text) if self.prompt_cursor.is_none() => {
+                    self.paste(&text);
+                    self.update_diff();
+                    self.refresh_screen()?;
+                }
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Inserts text the terminal says was pasted all at once, exactly as it was copied
+    fn paste(&mut self, text: &str) {
+        if self.text_field.read_only || self.hex_view.is_some() || self.lazy_view.is_some() {
+            self.notify("Buffer is read-only");
+            return;
+        }
+        let paste_mode = self.text_field.text.is_paste_mode();
+        self.text_field.text.set_paste_mode(true);
+        // Terminals often send the line breaks in a paste as carriage returns
+        self.text_field.insert_str(&text.replace("\r\n", "\n").replace('\r', "\n"));
+        self.text_field.text.set_paste_mode(paste_mode);
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.hex_view.is_some() || self.lazy_view.is_some() {
+            return;
+        }
+        // The wheel only moves the view, so the cursor and any selection stay where they are
+        let lines = match mouse.kind {
+            MouseEventKind::ScrollUp => -SCROLL_LINES,
+            MouseEventKind::ScrollDown => SCROLL_LINES,
+            MouseEventKind::Down(MouseButton::Left) => 0,
+            _ => return,
+        };
+        if lines != 0 {
+            let len = self.text_field.text.len();
+            self.text_field.cursor.scroll(lines, len);
+            return;
+        }
+        let (origin, size) = self.pane_regions()[self.active_pane()];
+        if mouse.column < origin.0 || mouse.row < origin.1 || mouse.column >= origin.0 + size.0 || mouse.row >= origin.1 + size.1 {
+            return;
+        }
+        // Clicks in the same place in quick succession count up to a triple-click, then start again
+        let clicks = match self.last_click {
+            Some((time, position, clicks)) if position == (mouse.column, mouse.row) && time.elapsed() < MULTI_CLICK_TIME => clicks % 3 + 1,
+            _ => 1,
+        };
+        self.last_click = Some((Instant::now(), (mouse.column, mouse.row), clicks));
+        self.text_field.click((mouse.column - origin.0) as usize, (mouse.row - origin.1) as usize, clicks);
+    }
+
+    fn resize(&mut self, win_size: (u16, u16)) {
+        self.win_size = win_size;
+        self.text_field.size = text_area_size(win_size);
+        self.resize_panes();
+        if let Some(hex_view) = &mut self.hex_view {
+            hex_view.set_size(self.text_field.size);
+        }
+        if let Some(lazy_view) = &mut self.lazy_view {
+            if let Err(error) = lazy_view.set_size(self.text_field.size) {
+                self.status_message = Some(format!("Could not read file: {}", error));
+            }
+        }
+    }
+
+    fn set_status_message(&mut self, message: Option<String>) {
+        self.status_message = message;
+    }
+
+    /// Tells the user something they tried couldn't be done, flashing the message in the status bar until the next key,
+    /// and ringing the terminal's bell too if the config asks for it
+    pub fn notify(&mut self, message: &str) {
+        self.set_status_message(Some(message.to_string()));
+        self.alert = true;
+        self.ring_bell = self.config.bell;
+    }
+
+    fn get_status_message(&self) -> String {
+        match &self.status_message {
+            Some(string) => string.clone(),
+            None if self.hex_view.is_some() => {
+                let hex_view = self.hex_view.as_ref().unwrap();
+                let modified = if hex_view.is_dirty() {" -- [+]"} else {""};
+                format!("Offset: {:#010x} -- {} bytes{}", hex_view.get_byte_offset(), hex_view.bytes().len(), modified)
+            }
+            None if self.lazy_view.is_some() => self.lazy_view.as_ref().unwrap().get_status(),
+            None => {
+                let (x, y) = self.text_field.cursor.get_position();
+                // Shown here as well as in the header, so unsaved changes are noticed wherever you're looking
+                let modified = if self.text_field.is_dirty() {" -- [+]"} else {""};
+                let syntax = if self.text_field.text.has_syntax() {""} else {" -- [no syntax]"};
+                let paste = if self.text_field.text.is_paste_mode() {" -- [paste]"} else {""};
+                let (x_offset, _) = self.text_field.cursor.get_offset();
+                let width = self.text_field.cursor.get_size().0 as usize;
+                let text = &self.text_field.text;
+                let truncated = if text.display_column(y, text.line_len(y)) > text.display_column(y, x_offset) + width {" -- (line truncated)"} else {""};
+                let fields = [
+                    ("{line}", y.to_string()),
+                    ("{col}", x.to_string()),
+                    ("{lines}", text.len().to_string()),
+                    ("{percent}", ((y + 1) * 100 / text.len()).to_string()),
+                    ("{lang}", text.syntax_name().unwrap_or("Plain text").to_string()),
+                    ("{mode}", String::from("text")),
+                    ("{encoding}", String::from("UTF-8")),
+                    ("{flags}", format!("{}{}{}{}", modified, syntax, paste, truncated)),
+                ];
+                fields.iter().fold(self.config.status_format.clone(), |status, (placeholder, value)| status.replace(placeholder, value))
+            }
+        }
+    }
+
+    /// The columns left of each pane for line numbers and change markers
+    fn gutter_width(&self) -> u16 {
+        if self.config.line_numbers == LineNumbers::Off {
+            return 2;
+        }
+        let lines = match &self.split {
+            Some(Split{other: Pane::Buffer(text_field, _), ..}) => self.text_field.text.len().max(text_field.text.len()),
+            _ => self.text_field.text.len(),
+        };
+        // The widest number, then a space and the change marker
+        lines.to_string().len() as u16 + 2
+    }
+
+    fn pane_regions(&self) -> Vec<((u16, u16), (u16, u16))> {
+        let gutter = self.gutter_width();
+        let (width, height) = ((self.text_field.size.0 + 2).saturating_sub(gutter), self.text_field.size.1);
+        match &self.split {
+            Some(Split{direction: SplitDirection::Horizontal, ..}) => {
+                let top = (height - 1) / 2;
+                vec![((gutter, 2), (width, top)), ((gutter, 3 + top), (width, height - 1 - top))]
+            }
+            Some(Split{direction: SplitDirection::Vertical, ..}) => {
+                // Each pane keeps its own gutter, with a one column separator between them
+                let left = width.saturating_sub(gutter + 1) / 2;
+                vec![((gutter, 2), (left, height)), ((2 * gutter + 1 + left, 2), (width.saturating_sub(gutter + 1 + left), height))]
+            }
+            None => vec![((gutter, 2), (width, height))],
+        }
+    }
+
+    fn active_pane(&self) -> usize {
+        match &self.split {
+            Some(split) if split.focus_second => 1,
+            _ => 0,
+        }
+    }
+
+    fn resize_panes(&mut self) {
+        let regions = self.pane_regions();
+        let active = self.active_pane();
+        self.text_field.cursor.set_size(regions[active].1, &self.text_field.text);
+        match self.split.as_mut().map(|split| &mut split.other) {
+            // A second view is onto the same text
+            Some(Pane::View(cursor)) => cursor.set_size(regions[1 - active].1, &self.text_field.text),
+            Some(Pane::Buffer(text_field, _)) => text_field.cursor.set_size(regions[1 - active].1, &text_field.text),
+            None => {}
+        }
+    }
+
+    pub fn refresh_screen(&mut self) -> std::io::Result<()> {
+        // Terminals that support synchronized output show the frame all at once, and others ignore it
+        queue!(&mut self.w, terminal::BeginSynchronizedUpdate)?;
+        if self.win_size.0 < MIN_WIN_SIZE.0 || self.win_size.1 < MIN_WIN_SIZE.1 {
+            let message: String = "Terminal too small".chars().take(self.win_size.0 as usize).collect();
+            queue!(&mut self.w, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0), style::Print(message), terminal::EndSynchronizedUpdate)?;
+            return self.w.flush();
+        }
+        self.print_header()?;
+        if let Some(hex_view) = &self.hex_view {
+            for i in 2..self.win_size.1-1 {
+                queue!(&mut self.w, cursor::MoveTo(0,i), style::Print("~"), terminal::Clear(ClearType::UntilNewLine))?;
+                hex_view.print_row(&mut self.w, (i-2) as usize)?;
+            }
+        } else if let Some(lazy_view) = &self.lazy_view {
+            for i in 2..self.win_size.1-1 {
+                queue!(&mut self.w, cursor::MoveTo(0,i), style::Print("~"), terminal::Clear(ClearType::UntilNewLine))?;
+                lazy_view.print_row(&mut self.w, (i-2) as usize)?;
+            }
+        } else {
+            self.text_field.text.update_syntax();
+            if let Some(Split{other: Pane::Buffer(text_field, _), ..}) = &mut self.split {
+                text_field.text.update_syntax();
+            }
+            // The gutter widens as the line count gains digits
+            self.resize_panes();
+            let regions = self.pane_regions();
+            let active = self.active_pane();
+            let gutter = self.gutter_width();
+            for (index, &(origin, size)) in regions.iter().enumerate() {
+                let (text_field, view) = match &self.split {
+                    Some(Split{other: Pane::View(cursor), ..}) if index != active => (&self.text_field, cursor),
+                    Some(Split{other: Pane::Buffer(text_field, _), ..}) if index != active => (&**text_field, &text_field.cursor),
+                    _ => (&self.text_field, &self.text_field.cursor),
+                };
+                let (_, cursor_y) = view.get_position();
+                let rows = text_field.layout(view, self.config.focus_wrap && index == active);
+                for y in 0..size.1 {
+                    let (line_index, start) = rows[y as usize];
+                    let wrapped = y > 0 && rows[y as usize - 1].0 == line_index;
+                    let number = match self.config.line_numbers {
+                        _ if line_index >= text_field.text.len() => None,
+                        // Only the first row of a wrapped line is numbered
+                        _ if wrapped => Some(usize::MAX),
+                        LineNumbers::Off => None,
+                        LineNumbers::Absolute => Some(line_index + 1),
+                        LineNumbers::Relative => Some(line_index.abs_diff(cursor_y)),
+                        LineNumbers::Hybrid if line_index == cursor_y => Some(line_index + 1),
+                        LineNumbers::Hybrid => Some(line_index.abs_diff(cursor_y)),
+                    };
+                    queue!(&mut self.w, cursor::MoveTo(origin.0 - gutter, origin.1 + y))?;
+                    match number {
+                        Some(usize::MAX) => {}
+                        Some(number) => queue!(&mut self.w, SetForegroundColor(Color::DarkGrey), style::Print(format!("{:>1$}", number, gutter as usize - 2)), SetForegroundColor(Color::Reset))?,
+                        None => queue!(&mut self.w, style::Print("~"))?,
+                    }
+                    queue!(&mut self.w, terminal::Clear(ClearType::UntilNewLine))?;
+                    text_field.print_line(&mut self.w, view, origin, y, (line_index, start), &self.config)?;
+                }
+            }
+            match &self.split {
+                Some(Split{direction: SplitDirection::Horizontal, ..}) => {
+                    let separator = "─".repeat(self.win_size.0 as usize);
+                    queue!(&mut self.w, cursor::MoveTo(0, regions[1].0.1 - 1), SetForegroundColor(Color::DarkGrey), style::Print(separator), SetForegroundColor(Color::Reset))?;
+                }
+                Some(Split{direction: SplitDirection::Vertical, ..}) => {
+                    queue!(&mut self.w, SetForegroundColor(Color::DarkGrey))?;
+                    for y in 0..regions[1].1.1 {
+                        queue!(&mut self.w, cursor::MoveTo(regions[1].0.0 - gutter - 1, regions[1].0.1 + y), style::Print("│"))?;
+                    }
+                    queue!(&mut self.w, SetForegroundColor(Color::Reset))?;
+                }
+                None => {}
+            }
+        }
+        let status_message = self.get_status_message();
+        queue!(&mut self.w, cursor::MoveTo(0,self.win_size.1-1), terminal::Clear(ClearType::UntilNewLine))?;
+        if std::mem::take(&mut self.ring_bell) {
+            queue!(&mut self.w, style::Print('\x07'))?;
+        }
+        if self.alert {
+            queue!(&mut self.w, style::SetAttribute(Attribute::Reverse), style::Print(status_message.as_str()), style::SetAttribute(Attribute::NoReverse))?;
+        } else {
+            queue!(&mut self.w, style::Print(status_message.as_str()))?;
+        }
+        let cursor_position = match &self.hex_view {
+            _ if self.prompt_cursor.is_some() => (self.prompt_cursor.unwrap(), self.win_size.1 - 1),
+            Some(hex_view) => hex_view.get_cursor_position(),
+            None if self.lazy_view.is_some() => self.lazy_view.as_ref().unwrap().get_cursor_position(),
+            None => self.text_field.get_cursor_position(self.pane_regions()[self.active_pane()].0, self.config.focus_wrap),
+        };
+        queue!(&mut self.w, cursor::MoveTo(cursor_position.0, cursor_position.1), self.config.cursor_shape.style())?;
+        if self.prompt_cursor.is_some() || self.hex_view.is_some() || self.lazy_view.is_some() || self.text_field.cursor_in_view(self.config.focus_wrap) {
+            queue!(&mut self.w, cursor::Show)?;
+        } else {
+            queue!(&mut self.w, cursor::Hide)?;
+        }
+        queue!(&mut self.w, terminal::EndSynchronizedUpdate)?;
+        self.w.flush()
+    }
+
+    fn save(&mut self) -> std::io::Result<()> {
+        if self.lazy_view.is_some() {
+            self.notify("Large files are opened read-only");
+            return Ok(());
+        }
+        if self.text_field.read_only && self.hex_view.is_none() {
+            self.notify("Buffer is read-only (turn it off with the readonly command)");
+            return Ok(());
+        }
+        let default = if let Some(name) = &self.file_name {
+            name.clone()
+        }else{
+            String::with_capacity(32)
+        };
+        let previous_name = self.file_name.clone();
+        self.file_name = prompt!(self, "Enter a path to save to:", default);
+        if let Some(name) = &self.file_name {
+            if self.file_name != previous_name && self.config.syntax_highlighting {
+                self.text_field.text.set_syntax(text::syntax_for_file(name));
+            }
+        }
+        if self.config.lint_on_save && self.file_name.is_some() && self.hex_view.is_none() {
+            let issues = lint::lint(&self.text_field.text).len();
+            if issues > 0 {
+                let message = format!("{} lint issues -- save anyway? (y/n)", issues);
+                if prompt!(self, &message, String::new()).as_deref() != Some("y") {
+                    self.set_status_message(Some(String::from("Not saved -- the lint command lists the issues")));
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(name) = &self.file_name {
+            // Keep the version on disk before it's overwritten
+            if self.config.backup && Path::new(name).is_file() {
+                fs::copy(name, format!("{}.bak", name))?;
+            }
+            if self.config.backup_count > 0 {
+                let dir = match &self.config.backup_dir {
+                    Some(dir) => dir.clone(),
+                    None => Path::new(name).parent().map_or(PathBuf::new(), Path::to_path_buf),
+                };
+                Backups::new(dir, self.config.backup_count).backup(Path::new(name))?;
+            }
+        }
+        match (&self.file_name, &mut self.hex_view) {
+            (Some(name), Some(hex_view)) => hex_view.save(name)?,
+            (Some(name), None) => {
+                if self.text_field.trim_whitespace {
+                    self.text_field.trim_trailing_whitespace();
+                }
+                if self.config.trim_trailing_blank_lines {
+                    self.text_field.trim_trailing_blank_lines();
+                }
+                self.text_field.save(name)?
+            }
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn load(&mut self) -> std::io::Result<()> {
+        let default = if let Some(name) = &self.file_name {
+            name.clone()
+        }else{
+            String::with_capacity(32)
+        };
+        let file_name = prompt!(self, "Enter a path to load from:", default);
+        match &file_name {
+            Some(name) => {
+                if let Err(error) = self.open(name) {
+                    self.set_status_message(Some(format!("Could not open {}: {}", name, error)));
+                    return Ok(());
+                }
+            },
+            None => {
+                self.text_field.reset();
+                self.hex_view = None;
+                self.lazy_view = None;
+            }
+        }
+        self.file_name = file_name;
+        Ok(())
+    }
+
+    fn toggle_hex_view(&mut self) {
+        if self.lazy_view.is_some() {
+            self.set_status_message(Some(String::from("Large files can't be shown in hex view")));
+            return;
+        }
+        match self.hex_view.take() {
+            Some(hex_view) => match String::from_utf8(hex_view.bytes().to_vec()) {
+                Ok(contents) => self.text_field.set_contents(contents, hex_view.is_dirty()),
+                Err(_) => {
+                    self.hex_view = Some(hex_view);
+                    self.set_status_message(Some(String::from("Cannot leave hex view: contents are not valid UTF-8")));
+                }
+            },
+            None => {
+                let bytes = self.text_field.text.contents().into_bytes();
+                self.hex_view = Some(HexView::new(bytes, self.text_field.size, self.text_field.is_dirty()));
+            }
+        }
+    }
+
+    fn handle_hex_key(&mut self, event: KeyEvent) {
+        let hex_view = self.hex_view.as_mut().unwrap();
+        match event {
+            KeyEvent {
+                code: direction @ (KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right),
+                modifiers: event::KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => hex_view.move_cursor(direction),
+            KeyEvent {
+                code: KeyCode::Tab,
+                kind: KeyEventKind::Press,
+                ..
+            } => hex_view.switch_pane(),
+            KeyEvent {
+                code: KeyCode::Char(c),
+                kind: KeyEventKind::Press,
+                ..
+            } => hex_view.insert_char(c),
+            _ => {}
+        }
+    }
+
+    fn handle_lazy_key(&mut self, event: KeyEvent) -> std::io::Result<()> {
+        let lazy_view = self.lazy_view.as_mut().unwrap();
+        match event {
+            KeyEvent {
+                code: direction @ (KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right),
+                kind: KeyEventKind::Press,
+                ..
+            } => lazy_view.move_cursor(direction)?,
+            KeyEvent {
+                code: KeyCode::Char(..) | KeyCode::Tab | KeyCode::Enter | KeyCode::Backspace,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.notify("Large files are opened read-only"),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn split_window(&mut self, direction: SplitDirection) {
+        if self.split.is_some() {
+            self.set_status_message(Some(String::from("The window is already split")));
+            return;
+        }
+        self.split = Some(Split{direction, other: Pane::View(self.text_field.cursor.clone()), focus_second: false, diff: false});
+        self.resize_panes();
+    }
+
+    fn switch_pane(&mut self) {
+        if let Some(split) = &mut self.split {
+            match &mut split.other {
+                Pane::View(cursor) => std::mem::swap(&mut self.text_field.cursor, cursor),
+                Pane::Buffer(text_field, file_name) => {
+                    std::mem::swap(&mut self.text_field, &mut **text_field);
+                    std::mem::swap(&mut self.file_name, file_name);
+                }
+            }
+            split.focus_second = !split.focus_second;
+            self.text_field.clamp_cursor();
+        }
+    }
+
+    fn close_pane(&mut self) {
+        if let Some(Split{other: Pane::Buffer(..), ..}) = &self.split {
+            if self.text_field.is_dirty() {
+                self.set_status_message(Some(String::from("Save this pane before closing it")));
+                return;
+            }
+        }
+        if let Some(split) = self.split.take() {
+            match split.other {
+                Pane::View(cursor) => self.text_field.cursor = cursor,
+                Pane::Buffer(text_field, file_name) => {
+                    self.text_field = *text_field;
+                    self.file_name = file_name;
+                }
+            }
+            self.text_field.diff_tags.clear();
+            self.text_field.diff_revision = None;
+            self.resize_panes();
+            self.text_field.clamp_cursor();
+        }
+    }
+
+    fn window_command(&mut self) -> std::io::Result<()> {
+        self.set_status_message(Some(String::from("Window: [s] split, [v] vertical split, [w] switch pane, [q] close pane")));
+        self.refresh_screen()?;
+        let event = self.read_key()?;
+        self.set_status_message(None);
+        if let KeyEvent{code: KeyCode::Char(c), kind: KeyEventKind::Press, ..} = event {
+            match c {
+                's' => self.split_window(SplitDirection::Horizontal),
+                'v' => self.split_window(SplitDirection::Vertical),
+                'w' => self.switch_pane(),
+                'q' => self.close_pane(),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&mut self) -> std::io::Result<()> {
+        let command = prompt!(self, "Command:", String::new());
+        match command.as_deref() {
+            Some("stats") => self.show_stats()?,
+            Some("syntax") => self.toggle_syntax(),
+            Some("find-in-files") => self.find_in_files()?,
+            Some("grep") => self.grep()?,
+            Some("build") => self.build()?,
+            Some("readonly") => self.toggle_read_only(),
+            Some("insert-file") => self.insert_file()?,
+            Some("write") => self.write_lines()?,
+            Some("reverse") => self.reverse_lines()?,
+            Some("outline") => self.outline()?,
+            Some("lint") => self.lint()?,
+            Some("insert-unicode") => self.insert_unicode()?,
+            Some("calc") => self.calculate()?,
+            Some("session-save") => self.save_session()?,
+            Some("retab-spaces") => self.retab(true),
+            Some("retab-tabs") => self.retab(false),
+            Some("prose") => {
+                self.config.prose = !self.config.prose;
+                let state = if self.config.prose {"on"} else {"off"};
+                self.set_status_message(Some(format!("Prose mode {}", state)));
+            }
+            Some("paste") => {
+                let paste_mode = !self.text_field.text.is_paste_mode();
+                self.text_field.text.set_paste_mode(paste_mode);
+                let state = if paste_mode {"on -- auto-indent and auto-pairs are off until it's turned off again"} else {"off"};
+                self.set_status_message(Some(format!("Paste mode {}", state)));
+            }
+            Some("line-endings") => {
+                self.config.show_line_endings = !self.config.show_line_endings;
+                let state = if self.config.show_line_endings {"shown"} else {"hidden"};
+                self.set_status_message(Some(format!("Line endings {}", state)));
+            }
+            Some("word-highlight") => {
+                self.config.highlight_word = !self.config.highlight_word;
+                let state = if self.config.highlight_word {"on"} else {"off"};
+                self.set_status_message(Some(format!("Word highlighting {}", state)));
+            }
+            Some("line-numbers") => {
+                self.config.line_numbers = self.config.line_numbers.next();
+                self.set_status_message(Some(format!("Line numbers: {}", self.config.line_numbers.name())));
+            }
+            Some("header-path") => {
+                self.config.header_path = self.config.header_path.next();
+                self.set_status_message(Some(format!("Header path: {}", self.config.header_path.name())));
+            }
+            Some(command) => self.set_status_message(Some(format!("Unknown command: {}", command))),
+            None => {}
+        }
+        Ok(())
+    }
+
+    fn insert_file(&mut self) -> std::io::Result<()> {
+        if self.text_field.read_only || self.hex_view.is_some() || self.lazy_view.is_some() {
+            self.notify("Buffer is read-only");
+            return Ok(());
+        }
+        let file_name = match prompt!(self, "Insert file:", String::new()) {
+            Some(file_name) => file_name,
+            None => return Ok(()),
+        };
+        match fs::read_to_string(&file_name) {
+            Ok(contents) => self.text_field.insert_str(&contents),
+            Err(error) => self.set_status_message(Some(format!("Could not read {}: {}", file_name, error))),
+        }
+        Ok(())
+    }
+
+    fn insert_unicode(&mut self) -> std::io::Result<()> {
+        if self.text_field.read_only || self.hex_view.is_some() || self.lazy_view.is_some() {
+            self.notify("Buffer is read-only");
+            return Ok(());
+        }
+        let code_point = match prompt!(self, "Code point (hex, e.g. 00A0):", String::new()) {
+            Some(code_point) => code_point,
+            None => return Ok(()),
+        };
+        let digits = code_point.trim().trim_start_matches("U+").trim_start_matches("u+");
+        match u32::from_str_radix(digits, 16).ok().and_then(char::from_u32) {
+            Some(c) => self.text_field.insert_literal(c),
+            None => self.set_status_message(Some(format!("Not a valid code point: {}", code_point))),
+        }
+        Ok(())
+    }
+
+    /// Prompts for an arithmetic expression and inserts its result at the cursor
+    fn calculate(&mut self) -> std::io::Result<()> {
+        if self.text_field.read_only || self.hex_view.is_some() || self.lazy_view.is_some() {
+            self.notify("Buffer is read-only");
+            return Ok(());
+        }
+        let expression = match prompt!(self, "Calculate:", String::new()) {
+            Some(expression) => expression,
+            None => return Ok(()),
+        };
+        match calc::evaluate(&expression) {
+            Ok(value) => {
+                let result = calc::format(value);
+                self.text_field.insert_str(&result);
+                self.set_status_message(Some(format!("{} = {}", expression.trim(), result)));
+            }
+            Err(error) => self.notify(&format!("Can't calculate {}: {}", expression.trim(), error)),
+        }
+        Ok(())
+    }
+
+    /// Inserts the next key exactly as typed, so a Tab stays a tab and Esc or Ctrl keys become control characters
+    fn quoted_insert(&mut self) -> std::io::Result<()> {
+        self.set_status_message(Some(String::from("Quoted insert: press the key to insert")));
+        self.refresh_screen()?;
+        let event = loop {
+            if let event @ KeyEvent{kind: KeyEventKind::Press, ..} = self.read_key()? {
+                break event;
+            }
+        };
+        self.set_status_message(None);
+        let c = match event {
+            KeyEvent{code: KeyCode::Char(c), modifiers, ..} if modifiers.contains(KeyModifiers::CONTROL) && c.is_ascii_alphabetic() => (c.to_ascii_uppercase() as u8 - b'@') as char,
+            KeyEvent{code: KeyCode::Char(c), ..} => c,
+            KeyEvent{code: KeyCode::Tab, ..} => '\t',
+            KeyEvent{code: KeyCode::Enter, ..} => '\r',
+            KeyEvent{code: KeyCode::Esc, ..} => '\x1b',
+            KeyEvent{code: KeyCode::Backspace, ..} => '\x7f',
+            _ => return Ok(()),
+        };
+        self.text_field.insert_literal(c);
+        Ok(())
+    }
+
+    /// Asks for a one-based range of lines, returning them as indices, the whole buffer if nothing was entered, or None if the range isn't valid
+    fn prompt_line_range(&mut self, message: &str) -> std::io::Result<Option<Range<usize>>> {
+        let len = self.text_field.text.len();
+        Ok(match prompt!(self, message, String::new()) {
+            None => Some(0..len),
+            Some(range) => match parse_line_range(&range) {
+                Some((first, last)) if first >= 1 && first <= last && last <= len => Some(first - 1..last),
+                _ => {
+                    self.set_status_message(Some(format!("Not a range of lines: {}", range)));
+                    None
+                }
+            },
+        })
+    }
+
+    fn reverse_lines(&mut self) -> std::io::Result<()> {
+        if self.text_field.read_only || self.hex_view.is_some() || self.lazy_view.is_some() {
+            self.notify("Buffer is read-only");
+            return Ok(());
+        }
+        let range = match self.prompt_line_range("Lines to reverse (e.g. 10-20, blank for all):")? {
+            Some(range) => range,
+            None => return Ok(()),
+        };
+        let start = range.start;
+        self.text_field.text.reverse_lines(range);
+        self.text_field.dirty = true;
+        self.text_field.move_to((0, start));
+        Ok(())
+    }
+
+    /// Writes a range of lines, or the whole buffer, to another file without changing which file is being edited
+    fn write_lines(&mut self) -> std::io::Result<()> {
+        if self.hex_view.is_some() || self.lazy_view.is_some() {
+            self.set_status_message(Some(String::from("Only text buffers can be written")));
+            return Ok(());
+        }
+        let range = match self.prompt_line_range("Lines to write (e.g. 10-20, blank for all):")? {
+            Some(range) => range,
+            None => return Ok(()),
+        };
+        let file_name = match prompt!(self, "Write to:", String::new()) {
+            Some(file_name) => file_name,
+            None => return Ok(()),
+        };
+        let count = range.len();
+        match self.text_field.text.save_range(range, &file_name) {
+            Ok(()) => self.set_status_message(Some(format!("Wrote {} lines to {}", count, file_name))),
+            Err(error) => self.set_status_message(Some(format!("Could not write {}: {}", file_name, error))),
+        }
+        Ok(())
+    }
+
+    fn retab(&mut self, to_spaces: bool) {
+        if self.text_field.read_only || self.hex_view.is_some() || self.lazy_view.is_some() {
+            self.notify("Buffer is read-only");
+            return;
+        }
+        let changed = self.text_field.text.retab(to_spaces);
+        if changed > 0 {
+            self.text_field.dirty = true;
+            self.text_field.clamp_cursor();
+        }
+        let style = if to_spaces {"spaces"} else {"tabs"};
+        self.set_status_message(Some(format!("Indented {} lines with {}", changed, style)));
+    }
+
+    fn toggle_read_only(&mut self) {
+        self.text_field.read_only = !self.text_field.read_only;
+        let state = if self.text_field.read_only {"on"} else {"off"};
+        self.set_status_message(Some(format!("Read-only {}", state)));
+    }
+
+    fn toggle_syntax(&mut self) {
+        self.config.syntax_highlighting = !self.config.syntax_highlighting;
+        let syntax_highlight = match &self.file_name {
+            _ if !self.config.syntax_highlighting => None,
+            Some(name) => text::syntax_for_file(name),
+            None => Some(Box::new(RustSyntax::new()) as Box<dyn text::SyntaxHighlight>),
+        };
+        if self.config.syntax_highlighting && syntax_highlight.is_none() {
+            self.set_status_message(Some(String::from("No syntax highlighting is available for this file type")));
+        }
+        self.text_field.text.set_syntax(syntax_highlight);
+    }
+
+    fn show_stats(&mut self) -> std::io::Result<()> {
+        if self.text_field.text.len() > 100_000 {
+            self.set_status_message(Some(String::from("Computing statistics…")));
+            self.refresh_screen()?;
+        }
+        let stats = self.text_field.text.stats();
+        self.set_status_message(Some(format!(
+            "{} lines -- {} words -- {} characters ({} without spaces) -- about {} min to read",
+            stats.lines, stats.words, stats.chars, stats.non_space_chars, stats.words.div_ceil(200),
+        )));
+        Ok(())
+    }
+
+    fn open_at(&mut self, file_name: String, x: usize, y: usize) {
+        match self.open(&file_name) {
+            Ok(()) => {
+                self.file_name = Some(file_name);
+                self.text_field.cursor.set_position(x, y);
+                self.text_field.clamp_cursor();
+            }
+            Err(error) => self.set_status_message(Some(format!("Could not open {}: {}", file_name, error))),
+        }
+    }
+
+    /// Shows `items` as a list over the text area, adding any that arrive on `receiver`, and returns the chosen index
+    fn pick<T: Display>(&mut self, title: &str, items: &mut Vec<T>, receiver: Option<Receiver<T>>) -> std::io::Result<Option<usize>> {
+        let mut list = ListView::new();
+        let mut receiving = receiver.is_some();
+        loop {
+            if let Some(receiver) = &receiver {
+                while receiving {
+                    match receiver.try_recv() {
+                        Ok(item) => items.push(item),
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => receiving = false,
+                    }
+                }
+            }
+            let progress = if receiving {" (searching…)"} else {""};
+            self.set_status_message(Some(format!("{} -- {} results{} -- Enter to open, Esc to cancel", title, items.len(), progress)));
+            self.print_header()?;
+            list.print(&mut self.w, items, (2, 2), self.text_field.size)?;
+            let status_message = self.get_status_message();
+            let (x, y) = list.get_cursor_position((2, 2));
+            queue!(&mut self.w, cursor::MoveTo(0, self.win_size.1.saturating_sub(1)), terminal::Clear(ClearType::UntilNewLine), style::Print(status_message), cursor::MoveTo(x, y))?;
+            self.w.flush()?;
+
+            match self.poll_key(Duration::from_millis(50))? {
+                Some(KeyEvent{code: KeyCode::Esc, ..}) => break,
+                Some(KeyEvent{code: KeyCode::Enter, ..}) if !items.is_empty() => {
+                    self.set_status_message(None);
+                    return Ok(Some(list.get_selected()));
+                }
+                Some(KeyEvent{code, kind: KeyEventKind::Press, ..}) => list.move_selection(code, items.len(), self.text_field.size.1 as usize),
+                _ => {}
+            }
+        }
+        self.set_status_message(None);
+        Ok(None)
+    }
+
+    /// Lists the key bindings over the text area, a page at a time, until a key is pressed after the last page
+    fn show_help(&mut self) -> std::io::Result<()> {
+        let height = self.win_size.1.saturating_sub(3).max(1) as usize;
+        let pages = KEY_BINDINGS.chunks(height).collect::<Vec<_>>();
+        let width = KEY_BINDINGS.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+        for (index, page) in pages.iter().enumerate() {
+            let more = if index + 1 < pages.len() {"any key for more, Esc to close"} else {"any key to close"};
+            self.set_status_message(Some(format!("Help -- page {} of {} -- {}", index + 1, pages.len(), more)));
+            self.print_header()?;
+            for y in 0..height as u16 {
+                queue!(&mut self.w, cursor::MoveTo(0, 2 + y), terminal::Clear(ClearType::UntilNewLine))?;
+                if let Some((keys, action)) = page.get(y as usize) {
+                    let line: String = format!("{:<2$}  {}", keys, action, width).chars().take((self.win_size.0 as usize).saturating_sub(2)).collect();
+                    queue!(&mut self.w, cursor::MoveTo(2, 2 + y), style::Print(line))?;
+                }
+            }
+            let status_message = self.get_status_message();
+            queue!(&mut self.w, cursor::MoveTo(0, self.win_size.1.saturating_sub(1)), terminal::Clear(ClearType::UntilNewLine), style::Print(status_message), cursor::Hide)?;
+            self.w.flush()?;
+            let event = loop {
+                if let KeyEvent{code, kind: KeyEventKind::Press, ..} = self.read_key()? {
+                    break code;
+                }
+            };
+            if event == KeyCode::Esc {
+                break;
+            }
+        }
+        self.set_status_message(None);
+        execute!(&mut self.w, cursor::Show)?;
+        Ok(())
+    }
+
+    /// Lists anything worth tidying up in the file, and jumps to the line of the one chosen
+    fn lint(&mut self) -> std::io::Result<()> {
+        let mut issues = lint::lint(&self.text_field.text);
+        if issues.is_empty() {
+            self.set_status_message(Some(String::from("No lint issues")));
+            return Ok(());
+        }
+        if let Some(index) = self.pick("Lint", &mut issues, None)? {
+            self.text_field.move_to((0, issues[index].line));
+        }
+        Ok(())
+    }
+
+    /// Lists the file's top-level definitions and jumps to the one chosen
+    fn outline(&mut self) -> std::io::Result<()> {
+        let symbols = self.text_field.text.symbols();
+        if symbols.is_empty() {
+            self.set_status_message(Some(String::from("No outline for this file")));
+            return Ok(());
+        }
+        let mut items: Vec<String> = symbols.iter().map(|(name, y)| format!("{:>5}  {}", y + 1, name)).collect();
+        if let Some(index) = self.pick("Outline", &mut items, None)? {
+            self.text_field.move_to((0, symbols[index].1));
+        }
+        Ok(())
+    }
+
+    fn find_in_files(&mut self) -> std::io::Result<()> {
+        let default_search = self.search_phrase.clone();
+        let phrase = match prompt!(self, "Find in files:", default_search) {
+            Some(phrase) => phrase,
+            None => return Ok(()),
+        };
+        let directory = match prompt!(self, "In directory:", String::from(".")) {
+            Some(directory) => directory,
+            None => return Ok(()),
+        };
+        let receiver = search::search_files(PathBuf::from(directory), phrase);
+        let mut results = Vec::new();
+        if let Some(index) = self.pick("Find in files", &mut results, Some(receiver))? {
+            let result = &results[index];
+            self.open_at(result.path.display().to_string(), result.column, result.line);
+        }
+        Ok(())
+    }
+
+    fn grep(&mut self) -> std::io::Result<()> {
+        let pattern = match prompt!(self, "Grep for:", String::new()) {
+            Some(pattern) => pattern,
+            None => return Ok(()),
+        };
+        self.set_status_message(Some(format!("Searching for {}…", pattern)));
+        self.refresh_screen()?;
+        match search::grep(&pattern) {
+            Ok(mut results) => {
+                if let Some(index) = self.pick("Grep", &mut results, None)? {
+                    let result = &results[index];
+                    self.open_at(result.path.display().to_string(), result.column, result.line);
+                }
+            }
+            Err(error) => self.set_status_message(Some(format!("Grep failed: {}", error))),
+        }
+        Ok(())
+    }
+
+    /// Runs the build command, offering the configured one to edit first, and lists the errors and warnings it reports
+    /// to jump to
+    fn build(&mut self) -> std::io::Result<()> {
+        let default_command = self.config.build_command.clone();
+        let command = match prompt!(self, "Build command:", default_command) {
+            Some(command) => command,
+            None => return Ok(()),
+        };
+        self.set_status_message(Some(format!("Running {}…", command)));
+        self.refresh_screen()?;
+        let (success, output) = match build::run(&command) {
+            Ok(result) => result,
+            Err(error) => {
+                self.set_status_message(Some(format!("Could not run {}: {}", command, error)));
+                return Ok(());
+            }
+        };
+        let mut diagnostics = build::parse_diagnostics(&output);
+        self.next_diagnostic = 0;
+        if diagnostics.is_empty() {
+            let first_line = output.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+            match success {
+                true => self.set_status_message(Some(String::from("Build succeeded"))),
+                false => self.notify(&format!("Build failed: {}", first_line)),
+            }
+        } else if let Some(index) = self.pick("Build", &mut diagnostics, None)? {
+            self.diagnostics = diagnostics;
+            self.goto_diagnostic(index);
+        } else {
+            self.diagnostics = diagnostics;
+        }
+        Ok(())
+    }
+
+    /// Moves to where one of the last build's diagnostics is, opening its file if it isn't the one being edited
+    fn goto_diagnostic(&mut self, index: usize) {
+        let diagnostic = &self.diagnostics[index];
+        let file_name = diagnostic.path.display().to_string();
+        let (x, y) = (diagnostic.column, diagnostic.line);
+        let message = format!("{}/{}: {}", index + 1, self.diagnostics.len(), diagnostic.message.trim());
+        if self.file_name.as_ref().is_some_and(|name| Path::new(name) == diagnostic.path) && self.lazy_view.is_none() && self.hex_view.is_none() {
+            self.text_field.move_to((x, y));
+        } else {
+            self.open_at(file_name, x, y);
+        }
+        self.next_diagnostic = index + 1;
+        self.set_status_message(Some(message));
+    }
+
+    fn next_diagnostic(&mut self) {
+        if self.diagnostics.is_empty() {
+            self.notify("No errors from the last build");
+        } else if self.next_diagnostic >= self.diagnostics.len() {
+            // Starting again from the first next time
+            self.next_diagnostic = 0;
+            self.notify("No more errors from the last build");
+        } else {
+            self.goto_diagnostic(self.next_diagnostic);
+        }
+    }
+
+    // What moving on from the last result (or back from the first) did
+    fn search_end_note(&self) -> &'static str {
+        if self.config.search_wraps {"Search wrapped"} else {"No more matches"}
+    }
+
+    fn find_phrase(editor: &mut Editor<W>, input: &String, key_code: KeyCode) -> Option<String> {
+        if editor.text_field.find_phrase(input, key_code) {
+            Some(String::from(editor.search_end_note()))
+        } else {
+            None
+        }
+    }
+
+    fn find(&mut self) -> std::io::Result<()> {
+        let previous_cursor = self.text_field.cursor.clone();
+        let default_search = self.search_phrase.clone();
+        self.text_field.search_data.set_wrap(self.config.search_wraps);
+        let message = if self.config.search_wraps {"Find:"} else {"Find (no wrap):"};
+        let phrase = prompt!(self, message, default_search, Self::find_phrase);
+        match phrase {
+            Some(phrase) => {
+                // The search may not have reached the end yet, so finish it to know whether anything was found
+                while self.text_field.search_data.is_searching() {
+                    self.text_field.continue_find();
+                }
+                if self.text_field.search_data.result_count() == 0 {
+                    self.notify(&format!("No matches for {}", phrase));
+                }
+                self.search_phrase = phrase;
+            }
+            None => self.text_field.cursor = previous_cursor,
+        }
+        self.text_field.end_find();
+        Ok(())
+    }
+
+    /// Prompts for a percentage and moves to the start of the line that far through the file
+    fn jump_to_percent(&mut self) -> std::io::Result<()> {
+        let input = match prompt!(self, "Jump to percentage:", String::new()) {
+            Some(input) => input,
+            None => return Ok(()),
+        };
+        let percent = match input.trim().trim_end_matches('%').parse::<usize>() {
+            Ok(percent) => min(percent, 100),
+            Err(_) => {
+                self.notify(&format!("Not a percentage: {}", input));
+                return Ok(());
+            }
+        };
+        match &mut self.lazy_view {
+            Some(lazy_view) => lazy_view.jump_to_percent(percent)?,
+            None => {
+                let last = self.text_field.text.len() - 1;
+                self.text_field.move_to((0, percent * last / 100));
+            }
+        }
+        Ok(())
+    }
+
+    fn find_word(&mut self, forward: bool) {
+        self.text_field.search_data.set_wrap(self.config.search_wraps);
+        match self.text_field.find_word(forward) {
+            Some((word, at_end)) => {
+                // Ctrl-F then starts from the same word
+                self.search_phrase = word;
+                if at_end && self.config.search_wraps {
+                    self.set_status_message(Some(String::from("Search wrapped")));
+                } else if at_end {
+                    self.notify("No more matches");
+                }
+            }
+            None => self.notify("The cursor isn't on a word"),
+        }
+    }
+
+    /// Jumps to the nearest definition of the word under the cursor before it, so jumping again steps back through the others
+    fn goto_definition(&mut self) {
+        let (x, y) = self.text_field.cursor.get_position();
+        let Some(bounds) = self.text_field.text.word_bounds(y, x) else {
+            self.notify("The cursor isn't on a word");
+            return;
+        };
+        let word = self.text_field.text.get_line(y)[bounds.clone()].to_string();
+        self.text_field.text.update_syntax();
+        let definitions = self.text_field.text.definitions(&word);
+        let before = definitions.iter().rposition(|&(definition_x, definition_y)| (definition_y, definition_x) < (y, bounds.start));
+        match before.or(definitions.len().checked_sub(1)) {
+            Some(index) => {
+                self.text_field.move_to(definitions[index]);
+                if definitions.len() > 1 {
+                    self.set_status_message(Some(format!("Definition {} of {}", index + 1, definitions.len())));
+                }
+            }
+            None => self.notify(&format!("No definition of {} found", word)),
+        }
+    }
+
+    fn quit(&mut self) -> std::io::Result<()>{
+        if !self.config.confirm_quit && !self.has_unsaved_changes() {
+            self.running = false;
+            return Ok(());
+        }
+        self.set_status_message(Some(String::from("Press Ctrl-C again to confirm quit. Press Esc to cancel")));
+        loop {
+            self.refresh_screen()?;
+            execute!(&mut self.w, cursor::Hide)?;
+            if let KeyEvent {code: c, modifiers: m, kind: KeyEventKind::Press, ..} = self.read_key()? {
+                match (c, m) {
+                    (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                        self.running = false;
+                        break;
+                    },
+                    (KeyCode::Esc, _) => break,
+                    _ => ()
+                }
+            }
+        }
+        // Show the cursor again and clear the prompt straight away, whether or not the editor is exiting
+        self.set_status_message(None);
+        execute!(&mut self.w, cursor::Show)?;
+        if self.running {
+            self.refresh_screen()?;
+        }
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> std::io::Result<()> {
+        while self.running {
+            self.refresh_screen()?;
+            let event = self.read_key()?;
+            self.handle_key(event)?;
+        }
+        // Put the terminal back now rather than when the editor is dropped, so the position isn't printed to the
+        // alternate screen
+        self.terminal = None;
+        self.print_position()
+    }
+
+    fn print_position(&self) -> std::io::Result<()> {
+        let (x, y) = self.text_field.cursor.get_position();
+        let position = ExitPosition{
+            file_name: self.file_name.as_deref(),
+            line: y + 1,
+            column: x + 1,
+            lines: self.text_field.text.len(),
+            dirty: self.has_unsaved_changes(),
+        };
+        match &self.position_output {
+            Some(PositionOutput::Stderr) => write!(io::stderr(), "{}", position),
+            Some(PositionOutput::File(path)) => fs::write(path, position.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    /// Carries out a single key press, reading any further keys a prompt it opens needs
+    pub fn handle_key(&mut self, event: KeyEvent) -> std::io::Result<()> {
+        self.set_status_message(None);
+        self.alert = false;
+        // A selection only lasts until the next key, except paging, which extends it, and dedenting, which can repeat
+        if !matches!(event.code, KeyCode::PageUp | KeyCode::PageDown | KeyCode::BackTab) {
+            self.text_field.selection = None;
+        }
+        // A count typed with Alt and digits applies to the next key, and any other key drops it
+        let pending_count = self.pending_count.take();
+        match event {
+            KeyEvent{
+                code: KeyCode::Char('c'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } => self.quit()?,
+            KeyEvent{
+                code: KeyCode::Char('s'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } => self.save()?,
+            KeyEvent{
+                code: KeyCode::Char('l'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } => self.load()?,
+            KeyEvent{
+                code: KeyCode::Char('b'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } => self.toggle_hex_view(),
+            KeyEvent{
+                code: KeyCode::F(1),
+                kind: KeyEventKind::Press,
+                ..
+            } => self.show_help()?,
+            KeyEvent{
+                code: KeyCode::Char('g'),
+                modifiers: event::KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                ..
+            } if self.hex_view.is_none() => self.jump_to_percent()?,
+            event if self.hex_view.is_some() => self.handle_hex_key(event),
+            event if self.lazy_view.is_some() => self.handle_lazy_key(event)?,
+            KeyEvent{
+                code: KeyCode::Char('w'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } => self.window_command()?,
+            KeyEvent{
+                code: KeyCode::Char('p'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } => self.command()?,
+            KeyEvent{
+                code: KeyCode::Char('f'),
+                modifiers: event::KeyModifiers::CONTROL,
+                ..
+            } => self.find()?,
+            KeyEvent{
+                code: KeyCode::Char('f'),
+                modifiers: event::KeyModifiers::ALT,
+                ..
+            } => self.find_in_files()?,
+            KeyEvent{
+                code: KeyCode::Char(key @ ('*' | '#')),
+                modifiers,
+                kind: KeyEventKind::Press,
+                ..
+            } if modifiers.contains(KeyModifiers::ALT) => self.find_word(key == '*'),
+            KeyEvent{
+                code: KeyCode::F(12),
+                kind: KeyEventKind::Press,
+                ..
+            } => self.goto_definition(),
+            KeyEvent{
+                code: KeyCode::F(8),
+                kind: KeyEventKind::Press,
+                ..
+            } => self.next_diagnostic(),
+            KeyEvent{
+                code: KeyCode::Char('z'),
+                modifiers: event::KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                ..
+            } => {
+                self.config.focus_wrap = !self.config.focus_wrap;
+                let state = if self.config.focus_wrap {"on"} else {"off"};
+                self.set_status_message(Some(format!("Focus wrap {}", state)));
+            }
+            KeyEvent{
+                code: KeyCode::Char(digit @ '0'..='9'),
+                modifiers: event::KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                ..
+            } => {
+                let count = pending_count.unwrap_or(0).saturating_mul(10).saturating_add(digit as usize - '0' as usize);
+                self.pending_count = Some(count);
+                self.set_status_message(Some(format!("Count: {}", count)));
+            }
+            KeyEvent {
+                code: direction @ (KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right),
+                modifiers: event::KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => {
+                for step in 0..pending_count.unwrap_or(1) {
+                    let position = self.text_field.cursor.get_position();
+                    self.text_field.move_cursor(direction);
+                    // Stop once the cursor can't go any further, saying so if it couldn't move at all
+                    if self.text_field.cursor.get_position() == position {
+                        if step == 0 {
+                            self.notify(if matches!(direction, KeyCode::Up | KeyCode::Left) {"Start of file"} else {"End of file"});
+                        }
+                        break;
+                    }
+                }
+            }
+            KeyEvent {
+                code: direction @ (KeyCode::PageUp | KeyCode::PageDown),
+                modifiers: event::KeyModifiers::NONE,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.text_field.page(if direction == KeyCode::PageUp {KeyCode::Up} else {KeyCode::Down}),
+            KeyEvent {
+                code: direction @ (KeyCode::Up | KeyCode::Down | KeyCode::Left),
+                modifiers: event::KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                ..
+            } => {
+                let y = self.text_field.cursor.get_position().1;
+                let text = &self.text_field.text;
+                let position = match direction {
+                    KeyCode::Up => text.same_indent_line(y, false),
+                    KeyCode::Down => text.same_indent_line(y, true),
+                    _ => text.parent_indent_line(y),
+                };
+                match position {
+                    Some(position) => self.text_field.move_to(position),
+                    None if direction == KeyCode::Left => self.notify("No less indented line above"),
+                    None => self.notify("No other line at this indentation in the block"),
+                }
+            }
+            // Prose navigation, for when there's no syntax to highlight or prose mode is on
+            KeyEvent {
+                code: direction @ (KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right),
+                modifiers: event::KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                ..
+            } if self.config.prose || !self.text_field.text.has_syntax() => {
+                let (x, y) = self.text_field.cursor.get_position();
+                let text = &self.text_field.text;
+                let position = match direction {
+                    KeyCode::Up => (0, text.paragraph_boundary(y, false)),
+                    KeyCode::Down => (0, text.paragraph_boundary(y, true)),
+                    KeyCode::Left => text.sentence_start((x, y), false),
+                    _ => text.sentence_start((x, y), true),
+                };
+                self.text_field.move_to(position);
+            }
+            KeyEvent {
+                code: KeyCode::Char(..) | KeyCode::Tab | KeyCode::BackTab | KeyCode::Enter | KeyCode::Backspace,
+                kind: KeyEventKind::Press,
+                ..
+            } if self.text_field.read_only => self.notify("Buffer is read-only"),
+            KeyEvent{
+                code: KeyCode::Char('v'),
+                modifiers: event::KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                ..
+            } => self.quoted_insert()?,
+            KeyEvent{
+                code: KeyCode::Char(key @ ('a' | 'x')),
+                modifiers: event::KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                ..
+            } => {
+                let amount = pending_count.unwrap_or(1) as i64;
+                if !self.text_field.increment_number(if key == 'a' {amount} else {-amount}) {
+                    self.set_status_message(Some(String::from("No number under or after the cursor")));
+                }
+            }
+            KeyEvent {
+                code: code @ (KeyCode::Char(..) | KeyCode::Tab),
+                kind: event::KeyEventKind::Press,
+                ..
+            } => self.text_field.insert_char(match code {
+                KeyCode::Tab => '\t',
+                KeyCode::Char(ch) => ch,
+                _ => unreachable!(),
+            }),
+            KeyEvent {
+                code: KeyCode::BackTab,
+                kind: event::KeyEventKind::Press,
+                ..
+            } => {
+                let dedented = self.text_field.dedent();
+                if !dedented {
+                    self.notify("Nothing to dedent");
+                }
+            }
+            KeyEvent {
+                code: KeyCode::Enter,
+                modifiers,
+                kind: event::KeyEventKind::Press,
+                ..
+            } if modifiers.contains(KeyModifiers::ALT) => self.text_field.open_line(modifiers.contains(KeyModifiers::SHIFT)),
+            KeyEvent {
+                code: KeyCode::Enter,
+                kind: event::KeyEventKind::Press,
+                ..
+            } => self.text_field.new_line(),
+            KeyEvent {
+                code: KeyCode::Backspace,
+                kind: event::KeyEventKind::Press,
+                ..
+            } => self.text_field.delete_char(),
+            _ => {}
+        }
+        self.update_diff();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What each row of the pane shows, drawn into a buffer
+    fn draw_rows(text_field: &TextField, config: &Config) -> Vec<Vec<u8>> {
+        text_field.layout(&text_field.cursor, false).into_iter().enumerate().map(|(y, row)| {
+            let mut output = Vec::new();
+            text_field.print_line(&mut output, &text_field.cursor, (2, 2), y as u16, row, config).unwrap();
+            output
+        }).collect()
+    }
+
+    #[test]
+    fn scrolling_keeps_the_selection_and_its_highlight() {
+        let mut text_field = TextField::new((20, 5));
+        text_field.text.set_text(&(0..30).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n"));
+        text_field.click(0, 1, 3);
+        assert_eq!(text_field.selection, Some((0, 1)));
+
+        // Paging extends the selection over more than a screen
+        text_field.page(KeyCode::Down);
+        text_field.page(KeyCode::Down);
+        assert_eq!(text_field.cursor.get_position(), (6, 11));
+        assert_eq!(text_field.selection, Some((0, 1)));
+        assert_eq!([0, 1, 10, 11, 12].map(|index| text_field.selected_range(index)), [None, Some(0..6), Some(0..7), Some(0..6), None]);
+
+        // The wheel scrolls back to the top, leaving the cursor off screen and the selection as it was
+        let len = text_field.text.len();
+        for _ in 0..4 {
+            text_field.cursor.scroll(-SCROLL_LINES, len);
+        }
+        assert_eq!(text_field.cursor.get_offset(), (0, 0));
+        assert_eq!(text_field.cursor.get_position(), (6, 11));
+        assert!(!text_field.cursor_in_view(false));
+
+        // Every visible row of the selection is still drawn highlighted, and the line above it isn't
+        let mut config = Config::new();
+        config.highlight_word = false;
+        let highlighted = draw_rows(&text_field, &config);
+        text_field.selection = None;
+        let plain = draw_rows(&text_field, &config);
+        let differs: Vec<bool> = highlighted.iter().zip(&plain).map(|(highlighted, plain)| highlighted != plain).collect();
+        assert_eq!(differs, [false, true, true, true, true]);
+    }
+
+    #[test]
+    fn dedenting_every_selected_line() {
+        let mut text_field = TextField::new((20, 5));
+        text_field.text.set_text("    a\n        b\nc\n    d");
+        // Selected from the middle of the first line to the end of the third
+        text_field.selection = Some((3, 0));
+        text_field.cursor.set_position(1, 2);
+        assert!(text_field.dedent());
+        assert_eq!((0..4).map(|index| text_field.text.get_line(index)).collect::<Vec<_>>(), ["a", "    b", "c", "    d"]);
+        // The selection's ends move with their lines' text
+        assert_eq!(text_field.selection, Some((0, 0)));
+        assert_eq!(text_field.cursor.get_position(), (1, 2));
+
+        text_field.selection = None;
+        assert!(!text_field.dedent());
+    }
+
+    #[test]
+    fn replacing_the_contents_keeps_the_cursor_where_it_can() {
+        let mut text_field = TextField::new((20, 5));
+        text_field.text.set_text("first\nsecond\nthird");
+        text_field.move_to((3, 1));
+        text_field.set_contents(String::from("one\ntwo\nthree\n"), true);
+        assert_eq!(text_field.cursor.get_position(), (3, 1));
+
+        text_field.move_to((5, 2));
+        text_field.set_contents(String::from("a\n"), true);
+        assert_eq!(text_field.cursor.get_position(), (1, 0));
+        assert_eq!(text_field.cursor.get_offset(), (0, 0));
+    }
+
+    #[test]
+    fn diff_colours_follow_a_paste() {
+        let dir = env::temp_dir().join(format!("text_editor_{}_diff_paste", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (old, new) = (dir.join("old.txt").display().to_string(), dir.join("new.txt").display().to_string());
+        fs::write(&old, "a\nb\n").unwrap();
+        fs::write(&new, "a\nb\n").unwrap();
+        let mut editor = Editor::headless(Vec::new(), (80, 24));
+        editor.open_diff(&old, &new);
+        assert!(editor.text_field.diff_tags.iter().all(|&tag| tag == DiffTag::Equal));
+
+        // Drawing after the paste highlights the text again, which mustn't stop the next key from diffing it again
+        editor.paste("x\n");
+        editor.refresh_screen().unwrap();
+        editor.handle_key(KeyEvent::from(KeyCode::Right)).unwrap();
+        assert_eq!(editor.text_field.text.len(), 3);
+        assert!(editor.text_field.diff_tags[0] != DiffTag::Equal);
+        assert!(editor.text_field.diff_tags[1..].iter().all(|&tag| tag == DiffTag::Equal));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn a_session_that_cannot_be_restored_is_reported() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let mut editor = Editor::headless(Vec::new(), (80, 24));
+        editor.open_args(&args(&["--session", "s.txt", "file.rs"]));
+        assert_eq!(editor.status_message.as_deref(), Some("--session can't be used with files to open"));
+        assert_eq!(editor.file_name, None);
+
+        let mut editor = Editor::headless(Vec::new(), (80, 24));
+        editor.open_args(&args(&["--session"]));
+        assert_eq!(editor.status_message.as_deref(), Some("--session needs the path of a session file"));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,187 @@
+use std::{env, fs, path::Path};
+
+use crate::{config::Indentation, text::{FinalNewline, Text}};
+
+#[derive(Clone, Copy)]
+enum IndentSize {
+    Columns(usize),
+    // An indent is as wide as a tab
+    Tab,
+}
+
+/// The settings `.editorconfig` files give for one file, each left as `None` when none of them set it
+#[derive(Default)]
+pub struct EditorConfig {
+    hard_tabs: Option<bool>,
+    indent_size: Option<IndentSize>,
+    tab_width: Option<usize>,
+    crlf: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+impl EditorConfig {
+    /// Reads the `.editorconfig` files in the file's directory and those above it, up to one marked `root = true`, with
+    /// the nearest taking precedence
+    pub fn find(file_name: &str) -> Self {
+        let path = env::current_dir().map_or(Path::new(file_name).to_path_buf(), |dir| dir.join(file_name));
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            if let Ok(contents) = fs::read_to_string(dir.join(".editorconfig")) {
+                let root = contents.lines()
+                    .take_while(|line| !line.trim().starts_with('['))
+                    .filter_map(|line| line.split_once('='))
+                    .any(|(key, value)| key.trim().eq_ignore_ascii_case("root") && value.trim().eq_ignore_ascii_case("true"));
+                files.push((dir, contents));
+                if root {
+                    break;
+                }
+            }
+        }
+        let mut editorconfig = Self::default();
+        for (dir, contents) in files.iter().rev() {
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            let relative = relative.components().map(|component| component.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/");
+            editorconfig.parse(contents, &relative);
+        }
+        editorconfig
+    }
+
+    // Applies the sections of one file that match the path, relative to the file's directory, in order
+    fn parse(&mut self, contents: &str, path: &str) {
+        let mut matches = false;
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                // A pattern without a slash matches the file's name in any directory
+                matches = match section.strip_prefix('/') {
+                    Some(section) => glob_matches(section, path),
+                    None if section.contains('/') => glob_matches(section, path),
+                    None => glob_matches(&format!("**/{}", section), &format!("/{}", path)),
+                };
+            } else if let Some((key, value)) = line.split_once('=').filter(|_| matches) {
+                self.set(&key.trim().to_lowercase(), &value.trim().to_lowercase());
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) {
+        let flag = match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+        match key {
+            "indent_style" => self.hard_tabs = match value {
+                "tab" => Some(true),
+                "space" => Some(false),
+                _ => None,
+            },
+            // Widths of zero are ignored like any other value that isn't understood, as indenting divides by them
+            "indent_size" => self.indent_size = match value {
+                "tab" => Some(IndentSize::Tab),
+                _ => value.parse().ok().filter(|&columns| columns > 0).map(IndentSize::Columns),
+            },
+            "tab_width" => self.tab_width = value.parse().ok().filter(|&columns| columns > 0),
+            // Lone carriage returns aren't supported, so they're left to what the file uses
+            "end_of_line" => self.crlf = match value {
+                "crlf" => Some(true),
+                "lf" => Some(false),
+                _ => None,
+            },
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = flag,
+            "insert_final_newline" => self.insert_final_newline = flag,
+            _ => {}
+        }
+    }
+
+    /// The indentation to use, with anything the `.editorconfig` files don't set taken from `base`
+    pub fn indentation(&self, base: Indentation) -> Indentation {
+        // Either of the widths defaults to the other
+        let indent_columns = match self.indent_size {
+            Some(IndentSize::Columns(columns)) => Some(columns),
+            _ => None,
+        };
+        let tab_width = self.tab_width.or(indent_columns).unwrap_or(base.tab_width);
+        let indent_size = match self.indent_size {
+            Some(IndentSize::Columns(columns)) => columns,
+            Some(IndentSize::Tab) => tab_width,
+            None => base.indent_size,
+        };
+        Indentation{tab_width, indent_size, hard_tabs: self.hard_tabs.unwrap_or(base.hard_tabs)}
+    }
+
+    /// Sets how the text is saved, with the final newline falling back to `final_newline` when it isn't set
+    pub fn apply(&self, text: &mut Text, final_newline: FinalNewline) {
+        text.set_final_newline_policy(match self.insert_final_newline {
+            Some(true) => FinalNewline::Always,
+            Some(false) => FinalNewline::Never,
+            None => final_newline,
+        });
+        if let Some(crlf) = self.crlf {
+            text.set_crlf(crlf);
+        }
+    }
+}
+
+/// Whether a path matches an EditorConfig glob, where `*` matches within a directory, `**` across them, `?` any one
+/// character, `[abc]`, `[a-z]` and `[!abc]` sets of characters, and `{one,two}` either of the alternatives
+fn glob_matches(pattern: &str, path: &str) -> bool {
+    if let Some(open) = pattern.find('{') {
+        if let Some(close) = pattern[open..].find('}').map(|close| open + close) {
+            return pattern[open + 1..close].split(',')
+                .any(|alternative| glob_matches(&format!("{}{}{}", &pattern[..open], alternative, &pattern[close + 1..]), path));
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    wildcard_matches(&pattern, &path)
+}
+
+fn wildcard_matches(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| wildcard_matches(rest, &path[i..])),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != '/')
+            .any(|i| wildcard_matches(rest, &path[i..])),
+        ['?', rest @ ..] => path.first().is_some_and(|&c| c != '/') && wildcard_matches(rest, &path[1..]),
+        ['[', rest @ ..] if rest.contains(&']') => {
+            let close = rest.iter().position(|&c| c == ']').unwrap_or(0);
+            let (negated, set) = match &rest[..close] {
+                ['!', set @ ..] => (true, set),
+                set => (false, set),
+            };
+            path.first().is_some_and(|&c| c != '/' && in_set(set, c) != negated) && wildcard_matches(&rest[close + 1..], &path[1..])
+        }
+        [c, rest @ ..] => path.first() == Some(c) && wildcard_matches(rest, &path[1..]),
+    }
+}
+
+fn in_set(set: &[char], c: char) -> bool {
+    match set {
+        [] => false,
+        [low, '-', high, rest @ ..] => (*low..=*high).contains(&c) || in_set(rest, c),
+        [first, rest @ ..] => *first == c || in_set(rest, c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_widths_are_ignored() {
+        let mut editorconfig = EditorConfig::default();
+        editorconfig.parse("[*]\nindent_size = 0\ntab_width = 0\n", "main.rs");
+        let indentation = editorconfig.indentation(Indentation{tab_width: 4, indent_size: 2, hard_tabs: false});
+        assert_eq!((indentation.tab_width, indentation.indent_size), (4, 2));
+
+        editorconfig.parse("[*.rs]\nindent_size = 3\n", "main.rs");
+        let indentation = editorconfig.indentation(Indentation{tab_width: 4, indent_size: 2, hard_tabs: false});
+        assert_eq!((indentation.tab_width, indentation.indent_size), (3, 3));
+    }
+}
\ No newline at end of file
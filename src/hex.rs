@@ -0,0 +1,149 @@
+use std::{cmp::{min, max}, io::Write, fs};
+
+use crossterm::{cursor, event::KeyCode, queue, style::{self, Color, SetForegroundColor}};
+
+use crate::text::{Cursor, Navigable};
+
+const BYTES_PER_ROW: usize = 16;
+const HEX_COLUMN: u16 = 12;
+const ASCII_COLUMN: u16 = HEX_COLUMN + 3 * BYTES_PER_ROW as u16 + 1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Hex,
+    Ascii,
+}
+
+struct Bytes(Vec<u8>);
+
+impl Navigable for Bytes {
+    fn len(&self) -> usize {
+        max(1, self.0.len().div_ceil(BYTES_PER_ROW))
+    }
+
+    fn line_len(&self, index: usize) -> usize {
+        // Bytes are edited in place, so the cursor stops on the last byte rather than after it
+        let row_len = min(self.0.len().saturating_sub(index * BYTES_PER_ROW), BYTES_PER_ROW);
+        row_len.saturating_sub(1)
+    }
+}
+
+pub struct HexView {
+    bytes: Bytes,
+    cursor: Cursor,
+    pane: Pane,
+    low_nibble: bool,
+    dirty: bool,
+}
+
+impl HexView {
+    pub fn new(bytes: Vec<u8>, size: (u16, u16), dirty: bool) -> Self {
+        Self{bytes: Bytes(bytes), cursor: Cursor::new(size), pane: Pane::Hex, low_nibble: false, dirty}
+    }
+
+    pub fn set_size(&mut self, size: (u16, u16)) {
+        self.cursor.set_size(size, &self.bytes);
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes.0
+    }
+
+    pub fn save(&mut self, file_name: &String) -> std::io::Result<()> {
+        fs::write(file_name, &self.bytes.0)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn get_byte_offset(&self) -> usize {
+        let (x, y) = self.cursor.get_position();
+        y * BYTES_PER_ROW + x
+    }
+
+    pub fn print_row(&self, w: &mut impl Write, y: usize) -> std::io::Result<()> {
+        let (_, y_offset) = self.cursor.get_offset();
+        let start = (y + y_offset) * BYTES_PER_ROW;
+        if start >= self.bytes.0.len() {
+            return Ok(());
+        }
+        let row = &self.bytes.0[start..min(start + BYTES_PER_ROW, self.bytes.0.len())];
+        let screen_y = 2 + y as u16;
+        let selected = self.get_byte_offset();
+        queue!(w, cursor::MoveTo(2, screen_y), SetForegroundColor(Color::DarkGrey), style::Print(format!("{:08x}", start)))?;
+        for (i, byte) in row.iter().enumerate() {
+            // Mark the selected byte in whichever pane the terminal cursor is not in
+            let colour = if start + i == selected && self.pane == Pane::Ascii {Color::Yellow} else {Color::Reset};
+            queue!(w, cursor::MoveTo(HEX_COLUMN + 3 * i as u16, screen_y), SetForegroundColor(colour), style::Print(format!("{:02x}", byte)))?;
+        }
+        for (i, byte) in row.iter().enumerate() {
+            let (c, colour) = if byte.is_ascii_graphic() || *byte == b' ' {
+                (*byte as char, Color::Reset)
+            } else {
+                ('.', Color::DarkGrey)
+            };
+            let colour = if start + i == selected && self.pane == Pane::Hex {Color::Yellow} else {colour};
+            queue!(w, cursor::MoveTo(ASCII_COLUMN + i as u16, screen_y), SetForegroundColor(colour), style::Print(c))?;
+        }
+        queue!(w, SetForegroundColor(Color::Reset))
+    }
+
+    pub fn get_cursor_position(&self) -> (u16, u16) {
+        let (x, y) = self.cursor.get_position();
+        let (_, y_offset) = self.cursor.get_offset();
+        let screen_y = (y - y_offset) as u16 + 2;
+        match self.pane {
+            Pane::Hex => (HEX_COLUMN + 3 * x as u16 + self.low_nibble as u16, screen_y),
+            Pane::Ascii => (ASCII_COLUMN + x as u16, screen_y),
+        }
+    }
+
+    pub fn move_cursor(&mut self, direction: KeyCode) {
+        self.cursor.move_cursor(&self.bytes, direction);
+        self.cursor.change_offset(&self.bytes);
+        self.low_nibble = false;
+    }
+
+    pub fn switch_pane(&mut self) {
+        self.pane = match self.pane {
+            Pane::Hex => Pane::Ascii,
+            Pane::Ascii => Pane::Hex,
+        };
+        self.low_nibble = false;
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let offset = self.get_byte_offset();
+        if offset >= self.bytes.0.len() {
+            return;
+        }
+        match self.pane {
+            Pane::Hex => {
+                if let Some(digit) = c.to_digit(16) {
+                    let byte = &mut self.bytes.0[offset];
+                    *byte = if self.low_nibble {
+                        (*byte & 0xf0) | digit as u8
+                    } else {
+                        (*byte & 0x0f) | (digit as u8) << 4
+                    };
+                    self.dirty = true;
+                    if self.low_nibble {
+                        self.move_cursor(KeyCode::Right);
+                    } else {
+                        self.low_nibble = true;
+                    }
+                }
+            }
+            Pane::Ascii => {
+                if c.is_ascii() {
+                    self.bytes.0[offset] = c as u8;
+                    self.dirty = true;
+                    self.move_cursor(KeyCode::Right);
+                }
+            }
+        }
+    }
+}
\ No newline at end of file
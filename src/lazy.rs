@@ -0,0 +1,172 @@
+use std::{cmp::{min, max}, fs::File, io::{Read, Seek, SeekFrom, Write}};
+
+use crossterm::{cursor, event::KeyCode, queue};
+
+use crate::text::{Cursor, Line, Navigable, PrintOptions};
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The lines of a file on disk, indexed as far as they have been scanned, with only a window of them in memory
+struct Lines {
+    file: File,
+    file_len: u64,
+    offsets: Vec<u64>,
+    scanned: u64,
+    window_start: usize,
+    window: Vec<Line>,
+}
+
+impl Lines {
+    fn is_indexed(&self) -> bool {
+        self.scanned >= self.file_len
+    }
+
+    fn index_to(&mut self, line: usize) -> std::io::Result<()> {
+        // A line's end is only known once the start of the next one has been found
+        self.index_while(|lines| lines.offsets.len() < line + 2)
+    }
+
+    // Scans on through the file for line starts for as long as `more` says there's further to go
+    fn index_while(&mut self, more: impl Fn(&Self) -> bool) -> std::io::Result<()> {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        while more(self) && !self.is_indexed() {
+            self.file.seek(SeekFrom::Start(self.scanned))?;
+            let read = self.file.read(&mut chunk)?;
+            if read == 0 {
+                self.file_len = self.scanned;
+                break;
+            }
+            for (i, byte) in chunk[..read].iter().enumerate() {
+                if *byte == b'\n' {
+                    self.offsets.push(self.scanned + i as u64 + 1);
+                }
+            }
+            self.scanned += read as u64;
+        }
+        Ok(())
+    }
+
+    fn line_end(&self, index: usize) -> u64 {
+        match self.offsets.get(index + 1) {
+            Some(next) => next - 1,
+            None => self.file_len,
+        }
+    }
+
+    fn load_window(&mut self, start: usize, count: usize) -> std::io::Result<()> {
+        self.index_to(start + count)?;
+        let end = min(start + count, Navigable::len(self));
+        if start == self.window_start && self.window.len() == end.saturating_sub(start) {
+            return Ok(());
+        }
+        self.window.clear();
+        self.window_start = start;
+        for index in start..end {
+            let mut bytes = vec![0; (self.line_end(index) - self.offsets[index]) as usize];
+            self.file.seek(SeekFrom::Start(self.offsets[index]))?;
+            self.file.read_exact(&mut bytes)?;
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+            self.window.push(Line::new(String::from_utf8_lossy(&bytes).into_owned()));
+        }
+        Ok(())
+    }
+
+    fn get(&self, index: usize) -> Option<&Line> {
+        self.window.get(index.checked_sub(self.window_start)?)
+    }
+}
+
+impl Navigable for Lines {
+    fn len(&self) -> usize {
+        if self.is_indexed() {
+            // A newline at the very end of the file doesn't start another line
+            let trailing_newline = self.offsets.len() > 1 && self.offsets.last() == Some(&self.file_len);
+            self.offsets.len() - trailing_newline as usize
+        } else {
+            max(1, self.offsets.len() - 1)
+        }
+    }
+
+    fn line_len(&self, index: usize) -> usize {
+        self.get(index).map_or(0, Line::len)
+    }
+
+    fn next_position(&self, index: usize, x: usize) -> usize {
+        self.get(index).map_or(x + 1, |line| line.next_boundary(x))
+    }
+
+    fn previous_position(&self, index: usize, x: usize) -> usize {
+        self.get(index).map_or(x - 1, |line| line.previous_boundary(x))
+    }
+}
+
+/// A read-only view of a file too large to load, which only reads the lines around the visible ones
+pub struct LazyView {
+    lines: Lines,
+    cursor: Cursor,
+    tab_width: usize,
+}
+
+impl LazyView {
+    pub fn open(file_name: &String, size: (u16, u16), tab_width: usize) -> std::io::Result<Self> {
+        let file = File::open(file_name)?;
+        let file_len = file.metadata()?.len();
+        let lines = Lines{file, file_len, offsets: vec![0], scanned: 0, window_start: 0, window: Vec::new()};
+        let mut lazy_view = Self{lines, cursor: Cursor::new(size), tab_width};
+        lazy_view.update_window()?;
+        Ok(lazy_view)
+    }
+
+    fn update_window(&mut self) -> std::io::Result<()> {
+        // Keep a screen of lines either side of the visible ones, so the cursor can always move onto its neighbours
+        let (_, y_offset) = self.cursor.get_offset();
+        let height = self.cursor.get_size().1 as usize;
+        self.lines.load_window(y_offset.saturating_sub(height), 3 * height)
+    }
+
+    pub fn set_size(&mut self, size: (u16, u16)) -> std::io::Result<()> {
+        self.cursor.set_size(size, &self.lines);
+        self.update_window()
+    }
+
+    /// Moves to the start of the line a percentage of the way through the file by size, like `less` does, which only
+    /// needs the file scanned up to there rather than all of its lines counted
+    pub fn jump_to_percent(&mut self, percent: usize) -> std::io::Result<()> {
+        let target = self.lines.file_len * percent as u64 / 100;
+        self.lines.index_while(|lines| lines.scanned < target)?;
+        let line = self.lines.offsets.partition_point(|&offset| offset <= target).saturating_sub(1);
+        self.cursor.set_position(0, min(line, Navigable::len(&self.lines) - 1));
+        self.cursor.change_offset(&self.lines);
+        self.update_window()
+    }
+
+    pub fn move_cursor(&mut self, direction: KeyCode) -> std::io::Result<()> {
+        self.cursor.move_cursor(&self.lines, direction);
+        self.cursor.change_offset(&self.lines);
+        self.update_window()
+    }
+
+    pub fn print_row(&self, w: &mut impl Write, y: usize) -> std::io::Result<()> {
+        let (x_offset, y_offset) = self.cursor.get_offset();
+        if let Some(line) = self.lines.get(y + y_offset) {
+            queue!(w, cursor::MoveTo(2, 2 + y as u16))?;
+            line.print(w, x_offset, self.cursor.get_size().0 as usize, &PrintOptions{tab_width: self.tab_width, overflow_column: None, highlight: &None, marks: &[]})?;
+        }
+        Ok(())
+    }
+
+    pub fn get_cursor_position(&self) -> (u16, u16) {
+        let (x, y) = self.cursor.get_position();
+        let (x_offset, y_offset) = self.cursor.get_offset();
+        let column = self.lines.get(y).map_or(0, |line| line.display_column(x, self.tab_width).saturating_sub(line.display_column(x_offset, self.tab_width)));
+        (column as u16 + 2, (y - y_offset) as u16 + 2)
+    }
+
+    pub fn get_status(&self) -> String {
+        let (_, y) = self.cursor.get_position();
+        let more = if self.lines.is_indexed() {""} else {"+"};
+        format!("Line: {} -- {}{} lines -- read-only", y, Navigable::len(&self.lines), more)
+    }
+}
\ No newline at end of file
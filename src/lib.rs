@@ -0,0 +1,18 @@
+//! The editing engine behind the text editor: buffers, cursors, searching and syntax highlighting,
+//! usable without a terminal.
+
+pub mod text;
+pub mod hex;
+pub mod diff;
+pub mod config;
+pub mod search;
+pub mod list;
+pub mod lazy;
+pub mod loader;
+pub mod editor;
+pub mod backup;
+pub mod session;
+pub mod editorconfig;
+pub mod lint;
+pub mod calc;
+pub mod build;
\ No newline at end of file
@@ -0,0 +1,67 @@
+use std::fmt;
+
+use crate::text::Text;
+
+/// Something worth tidying up in a file, on one of its lines
+pub struct Issue {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>5}  {}", self.line + 1, self.message)
+    }
+}
+
+/// Checks the text for mixed line endings, inconsistent indentation, trailing whitespace and characters that couldn't be
+/// read as UTF-8, without changing anything
+pub fn lint(text: &Text) -> Vec<Issue> {
+    let lines: Vec<&str> = (0..text.len()).map(|index| text.get_line(index)).collect();
+    let mut issues = Vec::new();
+    if let Some((crlf, lf)) = text.mixed_line_endings() {
+        let saved_as = if text.is_crlf() {"CRLF"} else {"LF"};
+        issues.push(Issue{line: 0, message: format!("Mixed line endings: {} CRLF and {} LF, which will all be saved as {}", crlf, lf, saved_as)});
+    }
+    issues.extend(indentation(&lines));
+    issues.extend(trailing_whitespace(&lines));
+    issues.extend(replacement_characters(&lines));
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+// Lines indented with both tabs and spaces, or with whichever of them most of the file doesn't use
+fn indentation(lines: &[&str]) -> Vec<Issue> {
+    let indents: Vec<&str> = lines.iter().map(|line| &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]).collect();
+    let tabs = indents.iter().filter(|indent| indent.starts_with('\t')).count();
+    let spaces = indents.iter().filter(|indent| indent.starts_with(' ')).count();
+    let (usual, unusual) = if tabs > spaces {('\t', "spaces")} else {(' ', "tabs")};
+    indents.iter().enumerate()
+        .filter_map(|(line, indent)| {
+            let message = if indent.contains(' ') && indent.contains('\t') {
+                String::from("Indented with both tabs and spaces")
+            } else if indent.chars().next().is_some_and(|c| c != usual) {
+                format!("Indented with {}, unlike most of the file", unusual)
+            } else {
+                return None;
+            };
+            Some(Issue{line, message})
+        })
+        .collect()
+}
+
+fn trailing_whitespace(lines: &[&str]) -> Vec<Issue> {
+    lines.iter().enumerate()
+        .filter(|(_, line)| line.ends_with([' ', '\t']))
+        .map(|(line, _)| Issue{line, message: String::from("Trailing whitespace")})
+        .collect()
+}
+
+// Bytes that weren't valid UTF-8 are replaced when a file is read (which opens it read-only), and would be saved as the
+// replacement
+fn replacement_characters(lines: &[&str]) -> Vec<Issue> {
+    lines.iter().enumerate()
+        .filter(|(_, line)| line.contains('\u{FFFD}'))
+        .map(|(line, _)| Issue{line, message: String::from("Replacement character, where the file may not have been UTF-8")})
+        .collect()
+}
\ No newline at end of file
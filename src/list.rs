@@ -0,0 +1,57 @@
+use std::{cmp::min, fmt::Display, io::Write};
+
+use crossterm::{cursor, event::KeyCode, queue, style::{self, Attribute, SetAttribute}, terminal::{self, ClearType}};
+
+/// A scrollable list with a single selected row, drawn over the text area
+#[derive(Default)]
+pub struct ListView {
+    selected: usize,
+    offset: usize,
+}
+
+impl ListView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn move_selection(&mut self, key_code: KeyCode, len: usize, height: usize) {
+        let last = len.saturating_sub(1);
+        self.selected = match key_code {
+            KeyCode::Up => self.selected.saturating_sub(1),
+            KeyCode::Down => min(self.selected + 1, last),
+            KeyCode::PageUp => self.selected.saturating_sub(height),
+            KeyCode::PageDown => min(self.selected + height, last),
+            _ => self.selected,
+        };
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        }
+        if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+    }
+
+    pub fn print<T: Display>(&self, w: &mut impl Write, items: &[T], origin: (u16, u16), size: (u16, u16)) -> std::io::Result<()> {
+        for y in 0..size.1 {
+            queue!(w, cursor::MoveTo(0, origin.1 + y), terminal::Clear(ClearType::UntilNewLine))?;
+            let index = self.offset + y as usize;
+            if let Some(item) = items.get(index) {
+                let text: String = item.to_string().chars().take(size.0 as usize).collect();
+                if index == self.selected {
+                    queue!(w, cursor::MoveTo(origin.0, origin.1 + y), SetAttribute(Attribute::Reverse), style::Print(text), SetAttribute(Attribute::Reset))?;
+                } else {
+                    queue!(w, cursor::MoveTo(origin.0, origin.1 + y), style::Print(text))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_cursor_position(&self, origin: (u16, u16)) -> (u16, u16) {
+        (origin.0, origin.1 + (self.selected - self.offset) as u16)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,50 @@
+use std::{fs::File, io::{self, Read}, path::PathBuf, sync::mpsc::{self, Receiver, Sender}, thread};
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+pub enum LoadProgress {
+    Reading(u64, u64),
+    // The text, and whether any of it had to be replaced for not being UTF-8
+    Done(io::Result<(String, bool)>),
+}
+
+/// Reads a file on a background thread, reporting how many bytes have been read after each chunk.
+/// Dropping the receiver abandons the read.
+pub fn read_file(path: PathBuf) -> Receiver<LoadProgress> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        if let Some(result) = read_chunks(&path, &sender) {
+            let _ = sender.send(LoadProgress::Done(result));
+        }
+    });
+    receiver
+}
+
+// Returns None once the receiver has been dropped
+fn read_chunks(path: &PathBuf, sender: &Sender<LoadProgress>) -> Option<io::Result<(String, bool)>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) => return Some(Err(error)),
+    };
+    let total = file.metadata().map_or(0, |metadata| metadata.len());
+    let mut bytes = Vec::with_capacity(total as usize);
+    let mut chunk = vec![0; CHUNK_SIZE];
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(read) => bytes.extend_from_slice(&chunk[..read]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Some(Err(error)),
+        }
+        sender.send(LoadProgress::Reading(bytes.len() as u64, total)).ok()?;
+    }
+    Some(Ok(decode(bytes)))
+}
+
+/// Reads bytes as UTF-8, replacing any that aren't valid with U+FFFD, and says whether there were any
+pub fn decode(bytes: Vec<u8>) -> (String, bool) {
+    match String::from_utf8(bytes) {
+        Ok(text) => (text, false),
+        Err(error) => (String::from_utf8_lossy(error.as_bytes()).into_owned(), true),
+    }
+}
\ No newline at end of file
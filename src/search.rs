@@ -0,0 +1,86 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}, process::{Command, Stdio}, sync::mpsc::{self, Receiver, Sender}, thread};
+
+use crate::text::Line;
+
+pub struct SearchResult {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    pub preview: String,
+}
+
+impl fmt::Display for SearchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path.display(), self.line + 1, self.preview.trim())
+    }
+}
+
+/// Searches every file below `root` on a background thread, sending each match as soon as it is found
+pub fn search_files(root: PathBuf, phrase: String) -> Receiver<SearchResult> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || search_path(&root, &phrase, &sender));
+    receiver
+}
+
+// Returns false once the receiver has been dropped, so an abandoned search stops walking
+fn search_path(path: &Path, phrase: &str, sender: &Sender<SearchResult>) -> bool {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return true,
+    };
+    if metadata.is_dir() {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return true,
+        };
+        let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .map(|entry| entry.path())
+            .collect();
+        paths.sort();
+        paths.iter().all(|path| search_path(path, phrase, sender))
+    } else if metadata.is_file() {
+        // Files that aren't valid UTF-8 text are skipped
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(_) => return true,
+        };
+        for (index, content) in contents.lines().enumerate() {
+            let line = Line::new(content.into());
+            if let Some(column) = line.find_phrase(phrase, 0) {
+                let result = SearchResult{path: path.to_path_buf(), line: index, column, preview: content.into()};
+                if sender.send(result).is_err() {
+                    return false;
+                }
+            }
+        }
+        true
+    } else {
+        true
+    }
+}
+
+/// Runs ripgrep, or grep when ripgrep isn't installed, over the current directory and collects its matches
+pub fn grep(pattern: &str) -> io::Result<Vec<SearchResult>> {
+    let ripgrep = Command::new("rg").args(["--line-number", "--no-heading", "--color", "never", "--", pattern, "."]).stdin(Stdio::null()).output();
+    let output = match ripgrep {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            Command::new("grep").args(["-rn", "--", pattern, "."]).stdin(Stdio::null()).output()?
+        }
+        result => result?,
+    };
+    // Both tools exit with 1 when nothing matched and 2 when something went wrong
+    match output.status.code() {
+        Some(0 | 1) => Ok(String::from_utf8_lossy(&output.stdout).lines().filter_map(parse_grep_line).collect()),
+        _ => Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string())),
+    }
+}
+
+/// Parses a `path:line:text` line of grep output
+pub fn parse_grep_line(line: &str) -> Option<SearchResult> {
+    let mut parts = line.splitn(3, ':');
+    let path = PathBuf::from(parts.next()?);
+    let line_number: usize = parts.next()?.parse().ok()?;
+    let preview = parts.next()?.to_string();
+    Some(SearchResult{path, line: line_number.checked_sub(1)?, column: 0, preview})
+}
\ No newline at end of file
@@ -0,0 +1,71 @@
+use std::{fmt, fs, io};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Layout {
+    Single,
+    Horizontal,
+    Vertical,
+    // Two files side by side, compared line by line
+    Diff,
+}
+
+impl Layout {
+    fn name(&self) -> &str {
+        match self {
+            Layout::Single => "single",
+            Layout::Horizontal => "horizontal",
+            Layout::Vertical => "vertical",
+            Layout::Diff => "diff",
+        }
+    }
+}
+
+/// The files open in each pane, in the order they're drawn, with where their cursors were and how the window was split
+pub struct Session {
+    pub layout: Layout,
+    pub focus: usize,
+    pub panes: Vec<(Option<String>, (usize, usize))>,
+}
+
+impl Session {
+    /// Reads a session saved as `key = value` lines, ignoring any it doesn't understand
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut session = Session{layout: Layout::Single, focus: 0, panes: Vec::new()};
+        for line in fs::read_to_string(path)?.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match (key.trim(), value.trim()) {
+                ("layout", "horizontal") => session.layout = Layout::Horizontal,
+                ("layout", "vertical") => session.layout = Layout::Vertical,
+                ("layout", "diff") => session.layout = Layout::Diff,
+                ("focus", focus) => session.focus = focus.parse().unwrap_or(0),
+                ("pane", pane) => {
+                    // The cursor comes first, so the file name can contain commas
+                    let mut parts = pane.splitn(3, ',').map(str::trim);
+                    let x = parts.next().and_then(|x| x.parse().ok()).unwrap_or(0);
+                    let y = parts.next().and_then(|y| y.parse().ok()).unwrap_or(0);
+                    let file_name = parts.next().filter(|name| !name.is_empty()).map(String::from);
+                    session.panes.push((file_name, (x, y)));
+                }
+                _ => {}
+            }
+        }
+        Ok(session)
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "layout = {}", self.layout.name())?;
+        writeln!(f, "focus = {}", self.focus)?;
+        for (file_name, (x, y)) in &self.panes {
+            writeln!(f, "pane = {}, {}, {}", x, y, file_name.as_deref().unwrap_or(""))?;
+        }
+        Ok(())
+    }
+}
\ No newline at end of file
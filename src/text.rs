@@ -1,146 +1,327 @@
-use std::{cmp::{min, max}, io::{Stdout, Write}, fs};
+use std::{cmp::{min, max}, fmt, io::{BufWriter, Write}, fs, ops::Range, path::Path};
 
-use crossterm::{event::KeyCode, style::{Color, SetForegroundColor, self}, queue};
+use crossterm::{event::KeyCode, style::{Attribute, Color, SetAttribute, SetForegroundColor, self}, queue};
 
+use crate::config::Indentation;
+
+// The default for both how wide tab characters are drawn and how many spaces the Tab key inserts
+pub const TAB_WIDTH: usize = 4;
+
+// Rows scanned per step, so a search over a large buffer doesn't hold up the find prompt
+const SEARCH_STEP_ROWS: usize = 10_000;
+
+// The UTF-8 byte order mark some files (mostly from Windows) start with
+const BOM: &str = "\u{feff}";
+
+/// A grid of rows the `Cursor` can move around in
+pub trait Navigable {
+    fn len(&self) -> usize;
+    fn line_len(&self, index: usize) -> usize;
+
+    /// Whether there are no rows at all, which the grids the cursor moves in never have
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The screen column position `x` on a line is drawn at
+    fn display_column(&self, _index: usize, x: usize) -> usize {
+        x
+    }
+
+    /// The position on a line drawn at a screen column, or the end of the line if it's shorter
+    fn position_at_column(&self, index: usize, column: usize) -> usize {
+        min(column, self.line_len(index))
+    }
+
+    /// The position one character on from `x`
+    fn next_position(&self, _index: usize, x: usize) -> usize {
+        x + 1
+    }
+
+    /// The position one character back from `x`, which mustn't be the start of the line
+    fn previous_position(&self, _index: usize, x: usize) -> usize {
+        x - 1
+    }
+
+    /// The nearest insert position to `(x, y)`: on a line that exists, and no further along it than its end
+    fn clamp_position(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        let y = min(y, self.len().saturating_sub(1));
+        (min(x, self.line_len(y)), y)
+    }
+}
+
+/// A position in a `Navigable` grid and the view scrolled over it.
+///
+/// The position is an insert position, the gap before the character at `x`, so it runs from 0 up to and including
+/// `line_len`, which is just after the line's last character, where appending to the line happens. Moving right from
+/// there goes on to the start of the next line, and moving up or down keeps to the same range on the new line.
+/// Placing the cursor with `set_position` doesn't check it, so positions from elsewhere go through `clamp_position`
 #[derive(Clone)]
 pub struct Cursor{
-    x: u16,
-    y: u16,
-    render_x: u16,
-    x_offset: u16,
-    y_offset: u16,
+    x: usize,
+    y: usize,
+    // The screen column moving up and down aims for, kept until the cursor moves sideways or is placed
+    desired_column: Option<usize>,
+    // The horizontal scroll from before moving up and down, returned to whenever the cursor fits in it
+    desired_offset: Option<usize>,
+    x_offset: usize,
+    y_offset: usize,
     size: (u16, u16),
 }
 
 impl Cursor {
+    /// Creates a cursor at the start of a view of the given size
     pub fn new(size: (u16, u16)) -> Self {
-        Self{x: 0, y: 0, render_x:0, x_offset: 0, y_offset: 0, size: size}
+        Self{x: 0, y: 0, desired_column: None, desired_offset: None, x_offset: 0, y_offset: 0, size: size}
     }
 
-    pub fn move_cursor(&mut self, text: &Text, direction: KeyCode) {
+    /// Moves one step in the direction of an arrow key, wrapping between lines
+    pub fn move_cursor(&mut self, text: &impl Navigable, direction: KeyCode) {
+        if matches!(direction, KeyCode::Up | KeyCode::Down) {
+            self.desired_offset.get_or_insert(self.x_offset);
+        } else {
+            self.desired_offset = None;
+        }
         match direction {
             KeyCode::Up => {
+                let desired_column = self.desired_column(text);
                 if self.y > 0 {
                     self.y -= 1;
-                    self.render_x = min(self.x, text.line_len(self.y as usize) as u16);
+                    self.x = text.position_at_column(self.y, desired_column);
                 }
+                self.desired_column = Some(desired_column);
             }
             KeyCode::Right => {
-                self.x = self.render_x;
-                if self.x < text.line_len(self.y as usize) as u16 {
-                    self.x += 1;
-                }else if self.y < text.len() as u16 - 1 {
+                if self.x < text.line_len(self.y) {
+                    self.x = text.next_position(self.y, self.x);
+                }else if self.y < text.len() - 1 {
                     self.y += 1;
                     self.x = 0;
                 }
-                self.render_x = self.x;
+                self.desired_column = None;
             }
             KeyCode::Down => {
-                if self.y < text.len() as u16 - 1 {
+                let desired_column = self.desired_column(text);
+                if self.y < text.len() - 1 {
                     self.y += 1;
-                    self.render_x = min(self.x, text.line_len(self.y as usize) as u16)
+                    self.x = text.position_at_column(self.y, desired_column);
                 }
+                self.desired_column = Some(desired_column);
             }
             KeyCode::Left => {
-                self.x = self.render_x;
                 if self.x > 0 {
-                    self.x -= 1;
+                    self.x = text.previous_position(self.y, self.x);
                 }else if self.y > 0 {
                     self.y -= 1;
-                    self.x = text.line_len(self.y as usize) as u16;
+                    self.x = text.line_len(self.y);
                 }
-                self.render_x = self.x;
+                self.desired_column = None;
             }
             _ => {}
         }
     }
 
-    pub fn change_offset(&mut self) {
-        if self.y < self.y_offset {   // Up
-            self.y_offset -= self.y_offset - self.y;
+    // The column moving up and down aims for, only measured when needed, as it means going along the line to the cursor
+    fn desired_column(&self, text: &impl Navigable) -> usize {
+        self.desired_column.unwrap_or_else(|| text.display_column(self.y, self.x))
+    }
+
+    /// Scrolls the view so the cursor is visible. The horizontal offset is a position on the line, but the view's width
+    /// is in columns, which a tab or a control character takes more than one of
+    pub fn change_offset(&mut self, text: &impl Navigable) {
+        let (width, height) = (self.size.0 as usize, self.size.1 as usize);
+        let column = text.display_column(self.y, self.x);
+        let shows_cursor = |offset: usize| offset <= self.x && column < text.display_column(self.y, offset) + width;
+        if let Some(desired_offset) = self.desired_offset.filter(|&offset| shows_cursor(offset)) {
+            self.x_offset = desired_offset;
         }
-        if self.render_x> self.size.0 + self.x_offset - 1 {  // Right
-            self.x_offset += self.render_x- (self.size.0 + self.x_offset - 1);
+        if self.y < self.y_offset {   // Up
+            self.y_offset = self.y;
         }
-        if self.y > self.size.1 + self.y_offset - 1 { // Down
-            self.y_offset += self.y - (self.size.1 + self.y_offset - 1);
+        if self.x < self.x_offset {   // Left
+            self.x_offset = self.x;
+        } else if !shows_cursor(self.x_offset) {  // Right
+            // The first position far enough along for the cursor to be on the view's last column
+            let first_column = column + 1 - width;
+            let offset = text.position_at_column(self.y, first_column);
+            self.x_offset = if text.display_column(self.y, offset) < first_column {text.next_position(self.y, offset)} else {offset};
         }
-        if self.render_x< self.x_offset {   // Left
-            self.x_offset -= self.x_offset - self.render_x;
+        if self.y >= self.y_offset + height { // Down
+            self.y_offset = self.y + 1 - height;
         }
     }
 
-    pub fn get_position(&self) -> (u16, u16) {
-        (self.render_x, self.y)
+    pub fn get_position(&self) -> (usize, usize) {
+        (self.x, self.y)
     }
 
-    pub fn set_position(&mut self, x: u16, y: u16) {
+    /// Brings the cursor back within the text and scrolls the view to it, for after the text is replaced or shrinks
+    pub fn clamp_to(&mut self, text: &impl Navigable) {
+        let (x, y) = text.clamp_position((self.x, self.y));
+        self.set_position(x, y);
+        self.change_offset(text);
+    }
+
+    pub fn set_position(&mut self, x: usize, y: usize) {
         self.x = x;
-        self.render_x = x;
         self.y = y;
+        self.desired_column = None;
+        self.desired_offset = None;
     }
 
-    pub fn get_offset(&self) -> (u16, u16) {
+    pub fn get_offset(&self) -> (usize, usize) {
         (self.x_offset, self.y_offset)
     }
 
+    pub fn set_offset(&mut self, x_offset: usize, y_offset: usize) {
+        self.x_offset = x_offset;
+        self.y_offset = y_offset;
+    }
+
+    /// Moves the view up or down by a number of lines without moving the cursor, keeping the first line in view
+    pub fn scroll(&mut self, lines: isize, len: usize) {
+        self.y_offset = self.y_offset.saturating_add_signed(lines).min(len.saturating_sub(1));
+    }
+
+    pub fn get_size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: (u16, u16), text: &impl Navigable) {
+        self.size = size;
+        self.change_offset(text);
+    }
+
+    /// The index of the line the cursor is on
     pub fn get_line_index(&self) -> usize {
-        self.y as usize
+        self.y
     }
 }
 
 pub struct SearchData {
-    results: Vec<(u16,u16)>,
+    results: Vec<(usize,usize)>,
     index: usize,
+    phrase: String,
+    next_row: Option<usize>,
+    highlighted: bool,
+    whole_word: bool,
+    // Whether moving on from the last result goes round to the first, and back from the first to the last
+    wrap: bool,
+}
+
+impl Default for SearchData {
+    fn default() -> Self {
+        Self{results: Vec::new(), index:0, phrase: String::new(), next_row: None, highlighted: false, whole_word: false, wrap: true }
+    }
 }
 
 impl SearchData {
     pub fn new() -> Self {
-        Self{results: Vec::new(), index:0 }
+        Self::default()
+    }
+
+    pub fn set_wrap(&mut self, wrap: bool) {
+        self.wrap = wrap;
+    }
+
+    /// Starts a new search, abandoning any unfinished one, and scans the first batch of rows
+    pub fn find_results(&mut self, phrase: &String, text: &mut Text) -> Option<(usize, usize)> {
+        self.start_search(phrase, false, text)
+    }
+
+    /// Like `find_results`, but only matching the word where it isn't part of a longer one
+    pub fn find_word(&mut self, word: &String, text: &mut Text) -> Option<(usize, usize)> {
+        self.start_search(word, true, text)
     }
 
-    pub fn find_results(&mut self, phrase: &String, text: &mut Text) -> Option<(u16, u16)> {
+    fn start_search(&mut self, phrase: &String, whole_word: bool, text: &mut Text) -> Option<(usize, usize)> {
+        // Tokenizing again clears the previous search's highlights
+        text.dirty_syntax = text.dirty_syntax || self.highlighted;
         text.update_syntax();
+        self.highlighted = false;
 
         self.results.clear();
-        if phrase.len() == 0 { return None; }
-        for row in 0..text.len() {
+        self.index = 0;
+        self.phrase = phrase.clone();
+        self.whole_word = whole_word;
+        self.next_row = if phrase.len() == 0 {None} else {Some(0)};
+        self.continue_search(text)
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.next_row.is_some()
+    }
+
+    pub fn result_count(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Scans the next batch of rows, returning the first result if it was found in this batch
+    pub fn continue_search(&mut self, text: &mut Text) -> Option<(usize, usize)> {
+        let start_row = self.next_row?;
+        let end_row = min(start_row + SEARCH_STEP_ROWS, text.len());
+        let first_new = self.results.len();
+        for row in start_row..end_row {
             let mut start = 0;
-            while let Some(result) = text.find_phrase(phrase, row, start) {
+            while let Some(result) = text.find_phrase(&self.phrase, row, start) {
                 let col = start + result;
-                self.results.push((col as u16, row as u16));
-                start = col + phrase.len();
+                if !self.whole_word || text.lines[row].is_whole_word(col, self.phrase.len()) {
+                    self.results.push((col, row));
+                }
+                start = col + self.phrase.len();
             }
         }
+        self.next_row = if end_row < text.len() {Some(end_row)} else {None};
 
-        for (x,y) in &self.results {
-            for i in 0..phrase.len() {
-                text.lines[*y as usize].highlight_types[i + (*x as usize)] = HighlightType::SearchResult;
+        for (x,y) in &self.results[first_new..] {
+            // Results are byte offsets, but there's a highlight type per character
+            let line = &mut text.lines[*y];
+            let first = line.content.get(..*x).map_or(0, |before| before.chars().count());
+            for i in first..first + self.phrase.chars().count() {
+                if let Some(highlight_type) = line.highlight_types.get_mut(i) {
+                    *highlight_type = HighlightType::SearchResult;
+                }
             }
         }
+        self.highlighted = self.highlighted || self.results.len() > first_new;
 
-        if self.results.len() > 0 {
-            Some(self.results[0])
+        if first_new == 0 {
+            self.results.first().copied()
         } else {
             None
         }
     }
 
-    pub fn get_next(&mut self) -> Option<(u16, u16)> {
+    /// Makes the last result at or before a position the current one, so the next and previous results follow on from it
+    pub fn select_at(&mut self, (x, y): (usize, usize)) {
+        self.index = self.results.iter().rposition(|&(result_x, result_y)| (result_y, result_x) <= (y, x)).unwrap_or(0);
+    }
+
+    /// Moves to the next result and returns it with whether it was already on the last one, in which case it wraps round
+    /// to the first, or stays on the last if the search doesn't wrap
+    pub fn get_next(&mut self) -> Option<((usize, usize), bool)> {
         if self.results.len() == 0 {
             None
         }else{
-            self.index = (self.index + 1) % self.results.len();
-            Some(self.results[self.index])
+            let at_end = self.index + 1 == self.results.len();
+            if self.wrap || !at_end {
+                self.index = (self.index + 1) % self.results.len();
+            }
+            Some((self.results[self.index], at_end))
         }
     }
 
-    pub fn get_previous(&mut self) -> Option<(u16, u16)> {
+    /// Moves to the previous result and returns it with whether it was already on the first one, in which case it wraps
+    /// round to the last, or stays on the first if the search doesn't wrap
+    pub fn get_previous(&mut self) -> Option<((usize, usize), bool)> {
         if self.results.len() == 0 {
             None
         }else{
-            self.index = (self.index + self.results.len() - 1) % self.results.len();
-            Some(self.results[self.index])
+            let at_start = self.index == 0;
+            if self.wrap || !at_start {
+                self.index = (self.index + self.results.len() - 1) % self.results.len();
+            }
+            Some((self.results[self.index], at_start))
         }
     }
 }
@@ -152,15 +333,45 @@ pub enum HighlightType {
     Keyword,
     Number, 
     Bracket,
+    // A bracket with no partner, or closed by the wrong kind
+    UnmatchedBracket,
     String,
     Comment,
     SearchResult,
 }
 
-trait SyntaxHighlight {
-    fn update_syntax(&self, lines: &mut Vec<Line>);
+pub trait SyntaxHighlight {
+    fn name(&self) -> &str;
+    fn update_syntax(&mut self, lines: &mut Vec<Line>);
     fn syntax_colour(&self, highlight_type: &HighlightType) -> Color;
 
+    /// The file's top-level definitions, such as functions or headings, with the line each is on, from lines that have
+    /// been through `update_syntax`
+    fn symbols(&self, _lines: &[Line]) -> Vec<(String, usize)> {
+        Vec::new()
+    }
+
+    /// Whether a string can carry on past the end of its line
+    fn allows_multiline_strings(&self) -> bool {
+        false
+    }
+
+    /// The characters that are closed automatically when typed, with what closes them
+    fn auto_pairs(&self) -> &[(char, char)] {
+        &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')]
+    }
+
+    /// Whether the line after one that runs up to the cursor as `line` goes a level further in, such as after an
+    /// opening bracket with no close
+    fn should_indent_after(&self, _line: &str) -> bool {
+        false
+    }
+
+    /// What a line can be typed as to take it back out a level, such as a closing bracket on its own
+    fn outdent_tokens(&self) -> &[&str] {
+        &[]
+    }
+
     fn word_len(&self, chars: &[char]) -> usize {
         let mut len = 0;
         while len < chars.len() {
@@ -196,13 +407,17 @@ trait SyntaxHighlight {
         let mut is_escaped = false;
         while len < chars.len() {
             let c = chars[len];
+            if c == '\n' && !self.allows_multiline_strings() {
+                return len;
+            }
             len += 1;
             if !is_escaped && c == start {
-                break;
+                return len;
             }
             is_escaped = c == '\\' && !is_escaped;
         }
-        len
+        // A string that's never closed is most likely still being typed, so only its own line is coloured
+        chars.iter().position(|&c| c == '\n').unwrap_or(chars.len())
     }
 
     fn single_line_comment_len(&self, chars: &[char], start: &str) -> usize{
@@ -269,50 +484,146 @@ trait SyntaxHighlight {
     }
 }
 
+/// Picks the syntax highlighting for a file from its extension
+pub fn syntax_for_file(file_name: &str) -> Option<Box<dyn SyntaxHighlight>> {
+    match Path::new(file_name).extension().and_then(|extension| extension.to_str()) {
+        Some("rs") => Some(Box::new(RustSyntax::new())),
+        Some("md") => Some(Box::new(MarkdownSyntax)),
+        Some("json") => Some(Box::new(JsonSyntax::new())),
+        Some("yaml" | "yml") => Some(Box::new(YamlSyntax)),
+        Some("sh" | "bash") => Some(Box::new(ShellSyntax::new())),
+        _ => None,
+    }
+}
+
+fn closing_bracket(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+/// A run of characters the highlighter read as one thing, such as a word, string or comment
+#[derive(Clone, Copy)]
+pub struct Token {
+    // The character the token starts at in its line
+    pub start: usize,
+    pub len: usize,
+    pub kind: HighlightType,
+}
+
+// Pushes a token's highlight type onto each line its characters fall on, splitting the token where it crosses lines
+fn push_highlight(lines: &mut [Line], line_index: &mut usize, chars: &[char], highlight_type: HighlightType) {
+    let mut new_token = true;
+    for c in chars {
+        if *c == '\n' {
+            *line_index += 1;
+            new_token = true;
+        }else{
+            let line = &mut lines[*line_index];
+            if new_token {
+                line.tokens.push(Token{start: line.highlight_types.len(), len: 0, kind: highlight_type});
+                new_token = false;
+            }
+            if let Some(token) = line.tokens.last_mut() {
+                token.len += 1;
+            }
+            line.highlight_types.push(highlight_type);
+        }
+    }
+}
+
+/// Every line's characters with a newline after each, for highlighters that tokenize across lines. It's kept between
+/// passes so tokenizing doesn't reallocate on every keystroke
+#[derive(Default)]
+struct CharBuffer(Vec<char>);
+
+impl CharBuffer {
+    // Fills the buffer from the lines, clearing their old highlighting, and lends it out until it's given back
+    fn fill(&mut self, lines: &mut [Line]) -> Vec<char> {
+        let mut chars = std::mem::take(&mut self.0);
+        chars.clear();
+        for line in lines {
+            chars.extend(line.content.chars());
+            chars.push('\n');
+            line.highlight_types.clear();
+            line.tokens.clear();
+        }
+        chars
+    }
+
+    fn give_back(&mut self, chars: Vec<char>) {
+        self.0 = chars;
+    }
+}
+
+#[derive(Default)]
 pub struct RustSyntax {
+    chars: CharBuffer,
+}
+
+impl RustSyntax {
+    pub fn new() -> Self {
+        Self::default()
+    }
 }
 
 impl SyntaxHighlight for RustSyntax {
-    fn update_syntax(&self, lines: &mut Vec<Line>) {
-        // Find colours
-        let mut chars = Vec::new();
-        for line in &mut *lines {
-            chars.append(&mut line.content.chars().clone().collect());
-            chars.push('\n');
-        };
-        let mut highlight_types = Vec::with_capacity(chars.len());
+    fn name(&self) -> &str {
+        "Rust"
+    }
+
+    fn update_syntax(&mut self, lines: &mut Vec<Line>) {
+        let chars = self.chars.fill(lines);
+        let keywords = ["impl","fn","pub","struct","enum","trait","use","for","if","while","else","break","return","continue","mod","macro_rules","true","false","loop","match","let","as","mut"];
+        let mut line_index = 0;
+        // Open brackets waiting for their partner, and brackets that will never get one, by line and character
+        let mut open_brackets = Vec::new();
+        let mut unmatched = Vec::new();
         let mut i = 0;
         while i < chars.len() {
             let word_len = self.word_len(&chars[i..]);
             if word_len > 0 {
-                let keywords = ["impl","fn","pub","struct","enum","trait","use","for","if","while","else","break","return","continue","mod","macro_rules","true","false","loop","match","let","as","mut"];
-                let word: String = chars[i..i+word_len].iter().collect();
-                let highlight_type = if keywords.contains(&word.as_str()) {
+                let is_keyword = keywords.iter().any(|keyword| keyword.len() == word_len && self.match_sequence(&chars[i..], keyword));
+                let highlight_type = if is_keyword {
                     HighlightType::Keyword
                 } else {
                     HighlightType::Identity
                 };
-                highlight_types.append(&mut vec![highlight_type; word_len]);
+                push_highlight(lines, &mut line_index, &chars[i..i+word_len], highlight_type);
                 i += word_len;
                 continue;
             }
 
             let number_len = self.number_len(&chars[i..]);
             if number_len > 0 {
-                highlight_types.append(&mut vec![HighlightType::Number; number_len]);
+                push_highlight(lines, &mut line_index, &chars[i..i+number_len], HighlightType::Number);
                 i += number_len;
                 continue;
             }
 
             let string_len = self.string_len(&chars[i..]);
             if string_len > 0 {
-                highlight_types.append(&mut vec![HighlightType::String; string_len]);
+                push_highlight(lines, &mut line_index, &chars[i..i+string_len], HighlightType::String);
                 i += string_len;
                 continue;
             }
 
             if self.is_bracket(&chars[i]){
-                highlight_types.push(HighlightType::Bracket);
+                let position = (line_index, lines[line_index].highlight_types.len());
+                match chars[i] {
+                    open @ ('(' | '[' | '{') => open_brackets.push((open, position)),
+                    // A close pairs with the nearest open of its kind, leaving any opened since unclosed
+                    close => match open_brackets.iter().rposition(|&(open, _)| closing_bracket(open) == close) {
+                        Some(index) => {
+                            unmatched.extend(open_brackets.drain(index + 1..).map(|(_, position)| position));
+                            open_brackets.pop();
+                        }
+                        None => unmatched.push(position),
+                    }
+                }
+                push_highlight(lines, &mut line_index, &chars[i..i+1], HighlightType::Bracket);
                 i += 1;
                 continue;
             }
@@ -322,27 +633,22 @@ impl SyntaxHighlight for RustSyntax {
                 self.multi_line_comment_len(&chars[i..], "/*", "*/"), 
             );
             if comment_len > 0 {
-                highlight_types.append(&mut vec![HighlightType::Comment; comment_len]);
+                push_highlight(lines, &mut line_index, &chars[i..i+comment_len], HighlightType::Comment);
                 i += comment_len;
                 continue;
             }
 
-            highlight_types.push(HighlightType::Standard);
+            push_highlight(lines, &mut line_index, &chars[i..i+1], HighlightType::Standard);
             i += 1;
         }
-
-        // Apply colours
-        for line in &mut *lines {
-            line.highlight_types = Vec::with_capacity(line.content.len());
-        }        
-        let mut line_index = 0;
-        for i in 0..chars.len() {
-            if chars[i] == '\n' {
-                line_index += 1;
-            }else{
-                lines[line_index].highlight_types.push(highlight_types[i]);
+        unmatched.extend(open_brackets.into_iter().map(|(_, position)| position));
+        for (y, x) in unmatched {
+            lines[y].highlight_types[x] = HighlightType::UnmatchedBracket;
+            if let Some(token) = lines[y].tokens.iter_mut().find(|token| token.start == x) {
+                token.kind = HighlightType::UnmatchedBracket;
             }
         }
+        self.chars.give_back(chars);
     }
 
     fn syntax_colour(&self, highlight_type: &HighlightType) -> Color {
@@ -351,179 +657,2090 @@ impl SyntaxHighlight for RustSyntax {
             HighlightType::Keyword => Color::Blue,
             HighlightType::Number => Color::Yellow,
             HighlightType::Bracket => Color::DarkYellow,
+            HighlightType::UnmatchedBracket => Color::Red,
             HighlightType::String => Color::Red,
             HighlightType::Comment => Color::DarkGreen,
             HighlightType::SearchResult => Color::Magenta,
             _ => Color::Reset
         }
     }
-}
 
-pub struct Line {
-    content: String,
-    highlight_types: Vec<HighlightType>,
-}
+    fn allows_multiline_strings(&self) -> bool {
+        true
+    }
 
-impl Line {
-    pub fn new(content: String) -> Self {
-        Self{content: content, highlight_types: Vec::new()}
+    // Definitions that start at the beginning of a line, after any `pub` or `pub(crate)`
+    fn symbols(&self, lines: &[Line]) -> Vec<(String, usize)> {
+        let mut symbols = Vec::new();
+        for (y, line) in lines.iter().enumerate() {
+            if line.content.starts_with(char::is_whitespace) {
+                continue;
+            }
+            let chars: Vec<char> = line.content.chars().collect();
+            let text = |token: &Token| chars.get(token.start..token.start + token.len).map_or(String::new(), |chars| chars.iter().collect());
+            let mut public = false;
+            for (index, token) in line.tokens.iter().enumerate() {
+                let word = text(token);
+                match token.kind {
+                    HighlightType::Keyword if word == "pub" => public = true,
+                    HighlightType::Standard if word.trim().is_empty() => {}
+                    HighlightType::Bracket | HighlightType::Identity if public => {}
+                    HighlightType::Keyword if word == "impl" => {
+                        let rest: String = chars[token.start + token.len..].iter().collect();
+                        symbols.push((format!("impl{}", rest.trim_end().trim_end_matches('{').trim_end()), y));
+                        break;
+                    }
+                    HighlightType::Keyword if ["fn", "struct", "enum", "trait", "mod"].contains(&word.as_str()) => {
+                        if let Some(name) = line.tokens[index + 1..].iter().find(|token| matches!(token.kind, HighlightType::Identity)) {
+                            symbols.push((format!("{} {}", word, text(name)), y));
+                        }
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+        }
+        symbols
     }
 
-    pub fn blank() -> Self {
-        Self{content: String::new(), highlight_types: Vec::new()}
+    // A quote is more often a lifetime or char than the start of a pair
+    fn auto_pairs(&self) -> &[(char, char)] {
+        &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')]
     }
 
-    pub fn insert(&mut self, index: usize, s: &str) {
-        self.content.insert_str(index, s);
+    fn should_indent_after(&self, line: &str) -> bool {
+        line.trim_end().ends_with(['{', '(', '['])
     }
 
-    pub fn delete_char(&mut self, index: usize) {
-        self.content.remove(index);
+    fn outdent_tokens(&self) -> &[&str] {
+        &["}", ")", "]"]
     }
+}
 
-    pub fn append(&mut self, line: &Line) {
-        self.content.push_str(line.content.as_str());
+/// Colours Markdown headings, so they stand out and can be listed in the outline
+pub struct MarkdownSyntax;
+
+// The level of a heading line, from the number of `#`s it starts with
+fn heading_level(line: &str) -> Option<usize> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let heading = (1..=6).contains(&level) && line[level..].chars().next().is_none_or(char::is_whitespace);
+    heading.then_some(level)
+}
+
+impl SyntaxHighlight for MarkdownSyntax {
+    fn name(&self) -> &str {
+        "Markdown"
     }
 
-    pub fn split_at(&mut self, index: usize) -> Line {
-        let new_line: String = self.content[index..].into();
-        self.content = self.content[..index].into();
-        Line::new(new_line)
+    fn update_syntax(&mut self, lines: &mut Vec<Line>) {
+        for index in 0..lines.len() {
+            lines[index].highlight_types.clear();
+            lines[index].tokens.clear();
+            let chars: Vec<char> = lines[index].content.chars().collect();
+            let highlight_type = if heading_level(&lines[index].content).is_some() {HighlightType::Keyword} else {HighlightType::Standard};
+            let mut line_index = index;
+            push_highlight(lines, &mut line_index, &chars, highlight_type);
+        }
     }
 
-    pub fn find_phrase(&self, phrase: &str, start: usize) -> Option<usize> {
-        self.content[start..].find(phrase)
+    fn syntax_colour(&self, highlight_type: &HighlightType) -> Color {
+        match highlight_type {
+            HighlightType::Keyword => Color::Blue,
+            HighlightType::SearchResult => Color::Magenta,
+            _ => Color::Reset
+        }
     }
 
-    pub fn len(&self) -> usize {
-        self.content.len()
+    // Headings, indented by their level
+    fn symbols(&self, lines: &[Line]) -> Vec<(String, usize)> {
+        lines.iter().enumerate()
+            .filter_map(|(y, line)| {
+                let level = heading_level(&line.content)?;
+                Some((format!("{}{}", "  ".repeat(level - 1), line.content[level..].trim()), y))
+            })
+            .collect()
     }
 
-    fn print(&self, w: &mut Stdout, start: usize, end: usize, highlight: &Option<Box<dyn SyntaxHighlight>>) -> std::io::Result<()> {
-        let start = min(start as usize, self.len());
-        let end = min(end as usize , self.len());
-        let mut previous_colour = Color::Reset;
-        for (i, c) in self.content[start..end].chars().enumerate() {
-            let colour = match (highlight, self.highlight_types.get(i + start)) {
-                (Some(syntax_highlight),Some(highlight_type)) => {
-                    syntax_highlight.syntax_colour(highlight_type)
-                }
-                _ => {
-                    Color::Reset
-                }   
-            };
-            if previous_colour != colour {
-                queue!(w, SetForegroundColor(colour))?;
-            }
-            previous_colour = colour;
-            queue!(w, style::Print(c))?;
-        }
-        queue!(w, SetForegroundColor(Color::Reset))?;
-        Ok(())
+    // Quotes are mostly apostrophes in prose
+    fn auto_pairs(&self) -> &[(char, char)] {
+        &[('(', ')'), ('[', ']')]
     }
 }
 
-pub struct Text {
-    lines: Vec<Line>,
-    syntax_highlight: Option<Box<dyn SyntaxHighlight>>,
+#[derive(Default)]
+pub struct JsonSyntax {
+    chars: CharBuffer,
 }
 
-impl Text{
+impl JsonSyntax {
     pub fn new() -> Self {
-        Self{lines: vec![Line::blank()], syntax_highlight: Some(Box::new(RustSyntax{})) }
+        Self::default()
     }
+}
 
-    pub fn load(&mut self, content: std::io::Result<String>) {
-        self.lines = match content {
-            Ok(contents) => {
-                let mut lines: Vec<Line> = contents.lines().map(|it| Line::new(it.into())).collect();
-                if lines.len() == 0 {lines.push(Line::blank())}
-                lines
-            },
-            _ => vec![Line::blank()]
-        };
-        self.update_syntax();
+impl SyntaxHighlight for JsonSyntax {
+    fn name(&self) -> &str {
+        "JSON"
     }
 
-    pub fn reset(&mut self) {
-        self.lines = vec![Line::blank()];
+    fn update_syntax(&mut self, lines: &mut Vec<Line>) {
+        let chars = self.chars.fill(lines);
+        let mut line_index = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            let string_len = self.string_len(&chars[i..]);
+            if string_len > 0 {
+                // A string followed by a colon is an object's key
+                let is_key = chars[i + string_len..].iter().find(|c| !c.is_whitespace()) == Some(&':');
+                let highlight_type = if is_key {HighlightType::Identity} else {HighlightType::String};
+                push_highlight(lines, &mut line_index, &chars[i..i+string_len], highlight_type);
+                i += string_len;
+                continue;
+            }
+
+            let number_len = self.number_len(&chars[i..]);
+            if number_len > 0 {
+                push_highlight(lines, &mut line_index, &chars[i..i+number_len], HighlightType::Number);
+                i += number_len;
+                continue;
+            }
+
+            let word_len = self.word_len(&chars[i..]);
+            if word_len > 0 {
+                let is_literal = ["true", "false", "null"].iter().any(|literal| literal.len() == word_len && self.match_sequence(&chars[i..], literal));
+                let highlight_type = if is_literal {HighlightType::Keyword} else {HighlightType::Standard};
+                push_highlight(lines, &mut line_index, &chars[i..i+word_len], highlight_type);
+                i += word_len;
+                continue;
+            }
+
+            let highlight_type = if self.is_bracket(&chars[i]) {HighlightType::Bracket} else {HighlightType::Standard};
+            push_highlight(lines, &mut line_index, &chars[i..i+1], highlight_type);
+            i += 1;
+        }
+        self.chars.give_back(chars);
     }
 
-    fn update_syntax(&mut self) {
-        if let Some(syntax_highlight) = &self.syntax_highlight {
-            syntax_highlight.update_syntax(&mut self.lines);
+    fn syntax_colour(&self, highlight_type: &HighlightType) -> Color {
+        match highlight_type {
+            HighlightType::Identity => Color::Cyan,
+            HighlightType::Keyword => Color::Blue,
+            HighlightType::Number => Color::Yellow,
+            HighlightType::Bracket => Color::DarkYellow,
+            HighlightType::String => Color::Red,
+            HighlightType::SearchResult => Color::Magenta,
+            _ => Color::Reset
         }
     }
 
-    pub fn save(&mut self, file_name: &String) -> std::io::Result<()> {
-        let mut file = fs::OpenOptions::new().write(true).create(true).open(file_name)?;
-        let strings: Vec<String> = self.lines.iter().map(|it| it.content.clone()).collect();
-        let contents = strings.join("\n");
-        file.set_len(contents.len() as u64)?;
-        file.write_all(contents.as_bytes())
+    // Strings only ever use double quotes
+    fn auto_pairs(&self) -> &[(char, char)] {
+        &[('[', ']'), ('{', '}'), ('"', '"')]
     }
 
-    pub fn print_line(&self, w: &mut Stdout, index: usize, start: u16, end: u16) -> std::io::Result<()> {
-        if index < self.lines.len() {
-            let line = &self.lines[index];
-            line.print(w, start as usize, end as usize, &self.syntax_highlight)?;
-        }
-        Ok(())
+    fn should_indent_after(&self, line: &str) -> bool {
+        line.trim_end().ends_with(['{', '['])
     }
 
-    pub fn insert_char(&mut self, c: char, cursor: &mut Cursor) {
-        let (x, y) = cursor.get_position();
-        let line = &mut self.lines[cursor.get_line_index()];
-        match c{
-            '\t' => {
-                line.insert(x as usize, "    ");
-                cursor.set_position(x + 4, y)
-            }
-            _ => {
-                line.insert(x as usize, c.to_string().as_str());
-                cursor.set_position(x + 1, y)
-            }
-        }
-        self.update_syntax();
+    fn outdent_tokens(&self) -> &[&str] {
+        &["}", "]"]
     }
+}
 
-    pub fn new_line(&mut self, cursor: &mut Cursor) {
-        let line_index = cursor.get_line_index();
-        let (x,y) = cursor.get_position();
-        let new_line = self.lines[line_index].split_at(x as usize);
-        self.lines.insert(line_index + 1, new_line);
-        self.update_syntax();
-        cursor.set_position(0, y + 1);
+#[derive(Default)]
+pub struct ShellSyntax {
+    chars: CharBuffer,
+}
+
+impl ShellSyntax {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    pub fn delete_char(&mut self, cursor: &mut Cursor) {
-        let line_index = cursor.get_line_index();
-        let (x, y) = cursor.get_position();
-        if x > 0 {
-            self.lines[line_index].delete_char(x as usize - 1);
-            cursor.set_position(x - 1, y);
-        }else if y > 0 {
-            let old_line = self.lines.remove(line_index);
-            let old_length = self.lines[line_index-1].len();
-            self.lines[line_index-1].append(&old_line);
-            cursor.set_position(old_length as u16, y-1);
+    // A variable expansion: `$NAME`, `${...}`, or a special parameter such as `$1` or `$?`
+    fn variable_len(&self, chars: &[char]) -> usize {
+        if chars[0] != '$' {
+            return 0;
+        }
+        match chars.get(1) {
+            Some('{') => chars.iter().position(|&c| c == '}' || c == '\n').map_or(chars.len(), |end| end + (chars[end] == '}') as usize),
+            Some(c) if c.is_ascii_digit() || "@#?$!*-".contains(*c) => 2,
+            Some(_) => 1 + self.word_len(&chars[1..]),
+            None => 0,
         }
-        self.update_syntax();
     }
+}
 
-    fn find_phrase(&self, phrase: &str, index: usize, start: usize) -> Option<usize> {
-        self.lines[index].find_phrase(phrase, start)
+impl SyntaxHighlight for ShellSyntax {
+    fn name(&self) -> &str {
+        "Shell"
     }
 
-    pub fn len(&self) -> usize {
-        self.lines.len()
-    }
+    fn update_syntax(&mut self, lines: &mut Vec<Line>) {
+        let chars = self.chars.fill(lines);
+        let keywords = ["if","then","else","elif","fi","for","in","do","done","case","esac","while","until","function","select","return","local","export"];
+        let mut line_index = 0;
+        let mut i = 0;
+        while i < chars.len() {
+            // A `#` only starts a comment at the start of a word
+            if i == 0 || chars[i - 1].is_whitespace() || chars[i - 1] == ';' {
+                let comment_len = self.single_line_comment_len(&chars[i..], "#");
+                if comment_len > 0 {
+                    push_highlight(lines, &mut line_index, &chars[i..i+comment_len], HighlightType::Comment);
+                    i += comment_len;
+                    continue;
+                }
+            }
 
-    fn line_len(&self, index: usize) -> usize {
-        if index < self.len() {
-            self.lines[index].len()
-        } else {
-            0
-        }
+            let string_len = self.string_len(&chars[i..]);
+            if string_len > 0 {
+                push_highlight(lines, &mut line_index, &chars[i..i+string_len], HighlightType::String);
+                i += string_len;
+                continue;
+            }
+
+            let variable_len = self.variable_len(&chars[i..]);
+            if variable_len > 1 {
+                push_highlight(lines, &mut line_index, &chars[i..i+variable_len], HighlightType::Identity);
+                i += variable_len;
+                continue;
+            }
+
+            let word_len = self.word_len(&chars[i..]);
+            if word_len > 0 {
+                let is_keyword = keywords.iter().any(|keyword| keyword.len() == word_len && self.match_sequence(&chars[i..], keyword));
+                let highlight_type = if is_keyword {HighlightType::Keyword} else {HighlightType::Standard};
+                push_highlight(lines, &mut line_index, &chars[i..i+word_len], highlight_type);
+                i += word_len;
+                continue;
+            }
+
+            let number_len = self.number_len(&chars[i..]);
+            if number_len > 0 {
+                push_highlight(lines, &mut line_index, &chars[i..i+number_len], HighlightType::Number);
+                i += number_len;
+                continue;
+            }
+
+            let highlight_type = if self.is_bracket(&chars[i]) {HighlightType::Bracket} else {HighlightType::Standard};
+            push_highlight(lines, &mut line_index, &chars[i..i+1], highlight_type);
+            i += 1;
+        }
+        self.chars.give_back(chars);
+    }
+
+    fn syntax_colour(&self, highlight_type: &HighlightType) -> Color {
+        match highlight_type {
+            HighlightType::Identity => Color::Cyan,
+            HighlightType::Keyword => Color::Blue,
+            HighlightType::Number => Color::Yellow,
+            HighlightType::Bracket => Color::DarkYellow,
+            HighlightType::String => Color::Red,
+            HighlightType::Comment => Color::DarkGreen,
+            HighlightType::SearchResult => Color::Magenta,
+            _ => Color::Reset
+        }
+    }
+
+    fn allows_multiline_strings(&self) -> bool {
+        true
+    }
+
+    // After a brace, and the keywords that start the body of an `if`, a loop or an `else`
+    fn should_indent_after(&self, line: &str) -> bool {
+        let line = line.trim_end();
+        line.ends_with('{') || line.split_whitespace().last().is_some_and(|word| ["then", "do", "else"].contains(&word))
+    }
+
+    fn outdent_tokens(&self) -> &[&str] {
+        &["}", "fi", "done", "else", "elif"]
+    }
+}
+
+/// Colours YAML a line at a time: keys, their values, comments and the dashes of list items
+pub struct YamlSyntax;
+
+// Splits a line of YAML into runs of characters of the same highlight type
+fn yaml_runs(chars: &[char]) -> Vec<(usize, HighlightType)> {
+    let mut runs = Vec::new();
+    let mut i = chars.iter().take_while(|c| c.is_whitespace()).count();
+    runs.push((i, HighlightType::Standard));
+    // List items, which can be nested on one line as in `- - item`
+    while chars.get(i) == Some(&'-') && chars.get(i + 1).is_none_or(|c| c.is_whitespace()) {
+        let spaces = chars[i + 1..].iter().take_while(|c| c.is_whitespace()).count();
+        runs.push((1, HighlightType::Bracket));
+        runs.push((spaces, HighlightType::Standard));
+        i += 1 + spaces;
+    }
+    // Colons and hashes inside quotes don't count
+    let mut quote = None;
+    let mut colon = None;
+    let mut comment = chars.len();
+    for j in i..chars.len() {
+        match (quote, chars[j]) {
+            (None, c @ ('"' | '\'')) => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if j == 0 || chars[j - 1].is_whitespace() => {
+                comment = j;
+                break;
+            }
+            (None, ':') if colon.is_none() && chars.get(j + 1).is_none_or(|c| c.is_whitespace()) => colon = Some(j),
+            _ => {}
+        }
+    }
+    match colon {
+        Some(colon) => {
+            runs.push((colon - i, HighlightType::Identity));
+            runs.push((1, HighlightType::Standard));
+            runs.push((comment - colon - 1, HighlightType::String));
+        }
+        None => runs.push((comment - i, HighlightType::String)),
+    }
+    runs.push((chars.len() - comment, HighlightType::Comment));
+    runs
+}
+
+impl SyntaxHighlight for YamlSyntax {
+    fn name(&self) -> &str {
+        "YAML"
+    }
+
+    fn update_syntax(&mut self, lines: &mut Vec<Line>) {
+        for index in 0..lines.len() {
+            lines[index].highlight_types.clear();
+            lines[index].tokens.clear();
+            let chars: Vec<char> = lines[index].content.chars().collect();
+            let mut start = 0;
+            for (len, highlight_type) in yaml_runs(&chars) {
+                let mut line_index = index;
+                push_highlight(lines, &mut line_index, &chars[start..start + len], highlight_type);
+                start += len;
+            }
+        }
+    }
+
+    fn syntax_colour(&self, highlight_type: &HighlightType) -> Color {
+        match highlight_type {
+            HighlightType::Identity => Color::Cyan,
+            HighlightType::Bracket => Color::DarkYellow,
+            HighlightType::String => Color::Red,
+            HighlightType::Comment => Color::DarkGreen,
+            HighlightType::SearchResult => Color::Magenta,
+            _ => Color::Reset
+        }
+    }
+
+    // A key with nothing after its colon starts a nested block
+    fn should_indent_after(&self, line: &str) -> bool {
+        let line = line.trim();
+        line.ends_with(':') && !line.starts_with('#')
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// The attribute that turns off one used to mark characters
+fn attribute_off(attribute: Attribute) -> Attribute {
+    match attribute {
+        Attribute::Underlined => Attribute::NoUnderline,
+        Attribute::Reverse => Attribute::NoReverse,
+        _ => Attribute::Reset,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineChange {
+    Unchanged,
+    Added,
+    Modified,
+    // Lines that were in the file have been deleted from just after this one
+    Removed,
+}
+
+impl LineChange {
+    pub fn colour(&self) -> Color {
+        match self {
+            LineChange::Added => Color::Green,
+            LineChange::Modified => Color::Yellow,
+            LineChange::Removed => Color::Red,
+            LineChange::Unchanged => Color::Reset,
+        }
+    }
+
+    /// What's drawn in the gutter, with removals marked at the bottom of the line they were after
+    pub fn marker(&self) -> char {
+        match self {
+            LineChange::Removed => '▁',
+            _ => '▎',
+        }
+    }
+}
+
+pub struct Line {
+    content: String,
+    highlight_types: Vec<HighlightType>,
+    tokens: Vec<Token>,
+    change: LineChange,
+    // Whether the line ended with CRLF rather than LF in the file, or None for a line that wasn't loaded from it
+    crlf: Option<bool>,
+}
+
+impl Line {
+    pub fn new(content: String) -> Self {
+        Self{content: content, highlight_types: Vec::new(), tokens: Vec::new(), change: LineChange::Unchanged, crlf: None}
+    }
+
+    pub fn blank() -> Self {
+        Self{content: String::new(), highlight_types: Vec::new(), tokens: Vec::new(), change: LineChange::Unchanged, crlf: None}
+    }
+
+    fn mark_modified(&mut self) {
+        if self.change == LineChange::Unchanged || self.change == LineChange::Removed {
+            self.change = LineChange::Modified;
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, s: &str) {
+        self.content.insert_str(index, s);
+        self.mark_modified();
+    }
+
+    pub fn delete_char(&mut self, index: usize) {
+        self.content.remove(index);
+        self.mark_modified();
+    }
+
+    pub fn append(&mut self, line: &Line) {
+        // Joining an empty line on leaves this one as it was
+        if !line.content.is_empty() {
+            self.content.push_str(line.content.as_str());
+            self.mark_modified();
+        }
+        // This line now ends where the other did
+        self.crlf = line.crlf;
+    }
+
+    pub fn split_at(&mut self, index: usize) -> Line {
+        let new_line: String = self.content[index..].into();
+        if index < self.content.len() {
+            self.content = self.content[..index].into();
+            self.mark_modified();
+        }
+        // The line's ending goes with the part after the split
+        let mut line = Line::new(new_line);
+        line.crlf = self.crlf.take();
+        line
+    }
+
+    pub fn find_phrase(&self, phrase: &str, start: usize) -> Option<usize> {
+        // Searching from inside a multi-byte character would panic, so begin at the next boundary
+        let mut boundary = min(start, self.len());
+        while !self.content.is_char_boundary(boundary) {
+            boundary += 1;
+        }
+        self.content[boundary..].find(phrase).map(|index| index + boundary - start)
+    }
+
+    // Whether the bytes from `start` aren't joined onto word characters either side
+    fn is_whole_word(&self, start: usize, len: usize) -> bool {
+        let before = self.content[..start].chars().next_back();
+        let after = self.content[start + len..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    }
+
+    /// The byte ranges where a word appears on its own, rather than as part of a longer word
+    pub fn word_occurrences(&self, word: &str) -> Vec<Range<usize>> {
+        self.content.match_indices(word)
+            .filter(|&(start, _)| self.is_whole_word(start, word.len()))
+            .map(|(start, _)| start..start + word.len())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// The byte offset of the character after the one at `index`, or the end of the line
+    pub fn next_boundary(&self, index: usize) -> usize {
+        self.content.get(index..).and_then(|after| after.chars().next()).map_or(self.content.len(), |c| index + c.len_utf8())
+    }
+
+    /// The byte offset of the character before `index`, or the start of the line
+    pub fn previous_boundary(&self, index: usize) -> usize {
+        self.content.get(..index).and_then(|before| before.chars().next_back()).map_or(0, |c| index - c.len_utf8())
+    }
+
+    /// The tokens the syntax highlighter found on the line, in order, or none without highlighting
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The screen column the character at byte offset `index` is drawn at, with tabs expanded to the next tab stop
+    pub fn display_column(&self, index: usize, tab_width: usize) -> usize {
+        let mut column = 0;
+        for (_, c) in self.content.char_indices().take_while(|&(start, _)| start < index) {
+            column += char_width(c, column, tab_width);
+        }
+        column
+    }
+
+    /// The byte offset of the character drawn at a screen column, stopping on a tab rather than skipping past it
+    pub fn position_at_column(&self, target: usize, tab_width: usize) -> usize {
+        let mut column = 0;
+        for (index, c) in self.content.char_indices() {
+            column += char_width(c, column, tab_width);
+            if column > target {
+                return index;
+            }
+        }
+        self.content.len()
+    }
+
+    /// Prints the part of the line from byte offset `start` that fits in `width` columns, returning how many it took
+    pub fn print(&self, w: &mut impl Write, start: usize, width: usize, options: &PrintOptions) -> std::io::Result<usize> {
+        let PrintOptions{tab_width, overflow_column, highlight, marks} = *options;
+        let first_column = self.display_column(start, tab_width);
+        let mut column = first_column;
+        let colouring = highlight.is_some() || overflow_column.is_some();
+        // Characters are printed in runs of the same colour, so uncoloured text is a single print
+        let mut run = String::new();
+        let mut previous_colour = Color::Reset;
+        let mut previous_mark = None;
+        // `start` and marks are byte offsets, but highlight types are per character
+        let chars = self.content.char_indices().enumerate().skip_while(|&(_, (offset, _))| offset < start);
+        for (i, (offset, c)) in chars {
+            let char_width = char_width(c, column, tab_width);
+            if column + char_width > first_column + width {
+                break;
+            }
+            let colour = match (highlight, self.highlight_types.get(i)) {
+                _ if c.is_control() && c != '\t' => {
+                    Color::DarkGrey
+                }
+                _ if !colouring => {
+                    Color::Reset
+                }
+                _ if overflow_column.is_some_and(|limit| column >= limit) => {
+                    Color::Red
+                }
+                (Some(syntax_highlight),Some(highlight_type)) => {
+                    syntax_highlight.syntax_colour(highlight_type)
+                }
+                _ => {
+                    Color::Reset
+                }   
+            };
+            // Marks are drawn with an attribute, which leaves the colours alone, and the first one covering a character wins
+            let mark = marks.iter().find(|(range, _)| range.contains(&offset)).map(|(_, attribute)| *attribute);
+            if previous_colour != colour || previous_mark != mark {
+                queue!(w, style::Print(&run), SetForegroundColor(colour))?;
+                if previous_mark != mark {
+                    if let Some(attribute) = previous_mark {
+                        queue!(w, SetAttribute(attribute_off(attribute)))?;
+                    }
+                    if let Some(attribute) = mark {
+                        queue!(w, SetAttribute(attribute))?;
+                    }
+                }
+                run.clear();
+            }
+            previous_colour = colour;
+            previous_mark = mark;
+            if c == '\t' {
+                run.push_str(&" ".repeat(char_width));
+            } else if c.is_ascii_control() {
+                // Control characters would be interpreted by the terminal, moving the cursor or starting an escape
+                // sequence, so they're shown in caret notation instead: ^M for a carriage return and ^[ for escape
+                run.push('^');
+                run.push((c as u8 ^ 0x40) as char);
+            } else {
+                // Other control characters have no caret notation, so show a placeholder
+                run.push(if c.is_control() { '\u{FFFD}' } else { c });
+            }
+            column += char_width;
+        }
+        queue!(w, style::Print(&run), SetForegroundColor(Color::Reset))?;
+        if let Some(attribute) = previous_mark {
+            queue!(w, SetAttribute(attribute_off(attribute)))?;
+        }
+        Ok(column - first_column)
+    }
+}
+
+/// How a line is drawn: tab stops, where it counts as too long, its syntax colours, and the byte ranges marked with
+/// an attribute (the first mark covering a character wins)
+#[derive(Clone, Copy)]
+pub struct PrintOptions<'a> {
+    pub tab_width: usize,
+    pub overflow_column: Option<usize>,
+    pub highlight: &'a Option<Box<dyn SyntaxHighlight>>,
+    pub marks: &'a [(Range<usize>, Attribute)],
+}
+
+// How many columns a character is drawn across at a column: a tab reaches the next tab stop, and an ASCII control
+// character takes two for its caret notation
+fn char_width(c: char, column: usize, tab_width: usize) -> usize {
+    match c {
+        '\t' => tab_width - column % tab_width,
+        c if c.is_ascii_control() => 2,
+        _ => 1,
+    }
+}
+
+/// Whether a saved file ends with a newline after its last line
+#[derive(Clone, Copy, PartialEq)]
+pub enum FinalNewline {
+    // As the file had when it was loaded, and always for new files
+    Preserve,
+    Always,
+    Never,
+}
+
+pub struct TextStats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub non_space_chars: usize,
+}
+
+pub struct Text {
+    lines: Vec<Line>,
+    syntax_highlight: Option<Box<dyn SyntaxHighlight>>,
+    dirty_syntax: bool,
+    // Counts the changes to the lines, so what's worked out from them elsewhere can tell when it's out of date
+    revision: u64,
+    tab_width: usize,
+    indent_size: usize,
+    hard_tabs: bool,
+    auto_pairs: bool,
+    // Whether Enter keeps the line's indentation, going a level further after a line that opens a block, and typing
+    // what closes a block on a line of its own takes it back out a level
+    auto_indent: bool,
+    // Whether text is being pasted, so it goes in as typed without auto-pairs or auto-indent
+    paste_mode: bool,
+    // Whether Backspace at the start of an indented line removes the indentation before joining it to the previous one
+    outdent_before_join: bool,
+    // Whether the file had a newline after its last line, which isn't kept as a line of its own
+    final_newline: bool,
+    final_newline_policy: FinalNewline,
+    // Whether lines are saved with Windows line endings
+    crlf: bool,
+    // How many lines ended with CRLF and LF when loaded, if there were some of each
+    mixed_line_endings: Option<(usize, usize)>,
+    // Whether the file started with a UTF-8 byte order mark, which is kept out of the buffer and written back on save
+    bom: bool,
+}
+
+impl fmt::Display for Text {
+    // The same as `contents`, so `to_string` gives the buffer as it would be saved
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.contents())
+    }
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self{lines: vec![Line::blank()], syntax_highlight: Some(Box::new(RustSyntax::new())), dirty_syntax: true, revision: 0, tab_width: TAB_WIDTH, indent_size: TAB_WIDTH, hard_tabs: false, auto_pairs: false, auto_indent: false, paste_mode: false, outdent_before_join: false, final_newline: true, final_newline_policy: FinalNewline::Preserve, crlf: false, mixed_line_endings: None, bom: false }
+    }
+}
+
+impl Text{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the buffer with the given contents, or a single blank line if they couldn't be read
+    pub fn load(&mut self, content: std::io::Result<String>) {
+        // A file that couldn't be read is treated as a new one
+        self.final_newline = content.as_ref().map_or(true, |contents| contents.ends_with('\n'));
+        self.mixed_line_endings = None;
+        self.bom = false;
+        self.lines = match content {
+            Ok(contents) => {
+                let contents = match contents.strip_prefix(BOM) {
+                    Some(rest) => {
+                        self.bom = true;
+                        rest
+                    }
+                    None => &contents,
+                };
+                // Files that only use CRLF are saved with it, and for the rest LF wins
+                let lf = contents.matches('\n').count();
+                let crlf = contents.matches("\r\n").count();
+                self.crlf = crlf > 0 && crlf == lf;
+                if crlf > 0 && crlf < lf {
+                    self.mixed_line_endings = Some((crlf, lf - crlf));
+                }
+                let mut lines: Vec<Line> = contents.split_inclusive('\n').map(|it| {
+                    let (content, crlf) = match it.strip_suffix("\r\n") {
+                        Some(content) => (content, true),
+                        None => (it.strip_suffix('\n').unwrap_or(it), false),
+                    };
+                    let mut line = Line::new(content.into());
+                    line.crlf = Some(crlf);
+                    line
+                }).collect();
+                if lines.len() == 0 {lines.push(Line::blank())}
+                lines
+            },
+            _ => vec![Line::blank()]
+        };
+        self.changed();
+    }
+
+    pub fn reset(&mut self) {
+        self.lines = vec![Line::blank()];
+        self.final_newline = true;
+        self.crlf = false;
+        self.mixed_line_endings = None;
+        self.bom = false;
+        self.changed();
+    }
+
+    pub fn set_final_newline_policy(&mut self, policy: FinalNewline) {
+        self.final_newline_policy = policy;
+    }
+
+    pub fn set_crlf(&mut self, crlf: bool) {
+        self.crlf = crlf;
+    }
+
+    pub fn is_crlf(&self) -> bool {
+        self.crlf
+    }
+
+    /// Whether the file started with a byte order mark, which saving writes again
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+
+    /// What ends a line: CRLF or LF as it did in the file, or what saving writes for lines added since, and nothing
+    /// after a last line the file didn't end with a newline
+    pub fn line_ending(&self, index: usize) -> Option<&'static str> {
+        let line = self.lines.get(index)?;
+        if index + 1 == self.lines.len() && !self.final_newline {
+            return None;
+        }
+        Some(if line.crlf.unwrap_or(self.crlf) {"\r\n"} else {"\n"})
+    }
+
+    /// How many lines ended with CRLF and how many with LF, when the file had both
+    pub fn mixed_line_endings(&self) -> Option<(usize, usize)> {
+        self.mixed_line_endings
+    }
+
+    fn newline(&self) -> &'static str {
+        if self.crlf {"\r\n"} else {"\n"}
+    }
+
+    // Whether a newline is written after the last line
+    fn ends_with_newline(&self) -> bool {
+        match self.final_newline_policy {
+            FinalNewline::Preserve => self.final_newline,
+            FinalNewline::Always => true,
+            FinalNewline::Never => false,
+        }
+    }
+
+    /// Sets how many columns a tab character is drawn across
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = max(1, tab_width);
+    }
+
+    pub fn get_tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Sets how many spaces the Tab key inserts
+    pub fn set_indent_size(&mut self, indent_size: usize) {
+        self.indent_size = max(1, indent_size);
+    }
+
+    pub fn get_indent_size(&self) -> usize {
+        self.indent_size
+    }
+
+    /// Sets whether the Tab key inserts a tab character rather than spaces
+    pub fn set_hard_tabs(&mut self, hard_tabs: bool) {
+        self.hard_tabs = hard_tabs;
+    }
+
+    /// Sets whether typing the opening character of one of the syntax's pairs also inserts its closing character
+    pub fn set_auto_pairs(&mut self, auto_pairs: bool) {
+        self.auto_pairs = auto_pairs;
+    }
+
+    pub fn set_auto_indent(&mut self, auto_indent: bool) {
+        self.auto_indent = auto_indent;
+    }
+
+    /// Sets whether auto-pairs and auto-indent are held off, so pasted text lands exactly as it was copied
+    pub fn set_paste_mode(&mut self, paste_mode: bool) {
+        self.paste_mode = paste_mode;
+    }
+
+    pub fn is_paste_mode(&self) -> bool {
+        self.paste_mode
+    }
+
+    // What one level of indentation is
+    fn indent_step(&self) -> String {
+        if self.hard_tabs {String::from("\t")} else {" ".repeat(self.indent_size)}
+    }
+
+    // With auto-indent, typing the last of a token like `}` or `fi` as all there is on a line takes the line back out a
+    // level, unless it's already no further in than the line above. `words` picks whether it's word tokens being checked,
+    // which only count once the line is finished, or the others, which count as soon as they're typed
+    fn outdent_token(&mut self, cursor: &mut Cursor, words: bool) {
+        let (x, y) = cursor.get_position();
+        let content = &self.lines[y].content;
+        let typed = content.trim_start_matches([' ', '\t']);
+        let is_token = self.syntax_highlight.as_ref().is_some_and(|syntax_highlight| {
+            syntax_highlight.outdent_tokens().iter().any(|token| *token == typed && token.starts_with(is_word_char) == words)
+        });
+        if !self.auto_indent || self.paste_mode || !is_token || x != content.len() {
+            return;
+        }
+        let above = (0..y).rev().find_map(|index| self.indent_width(index));
+        if self.indent_width(y).is_some_and(|width| width > 0 && above.is_none_or(|above| width >= above)) {
+            let removed = self.dedent_line(y);
+            cursor.set_position(x - removed, y);
+        }
+    }
+
+    pub fn set_outdent_before_join(&mut self, outdent_before_join: bool) {
+        self.outdent_before_join = outdent_before_join;
+    }
+
+    pub fn set_indentation(&mut self, indentation: Indentation) {
+        self.set_tab_width(indentation.tab_width);
+        self.set_indent_size(indentation.indent_size);
+        self.set_hard_tabs(indentation.hard_tabs);
+    }
+
+    pub fn syntax_name(&self) -> Option<&str> {
+        self.syntax_highlight.as_ref().map(|syntax_highlight| syntax_highlight.name())
+    }
+
+    pub fn has_syntax(&self) -> bool {
+        self.syntax_highlight.is_some()
+    }
+
+    pub fn set_syntax(&mut self, syntax_highlight: Option<Box<dyn SyntaxHighlight>>) {
+        self.syntax_highlight = syntax_highlight;
+        self.dirty_syntax = true;
+    }
+
+    /// A number that goes up whenever the lines change, and stays the same otherwise
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    // Called after any change to the lines
+    fn changed(&mut self) {
+        self.dirty_syntax = true;
+        self.revision += 1;
+    }
+
+    /// Re-tokenizes the buffer, unless nothing has changed since it was last tokenized
+    pub fn update_syntax(&mut self) {
+        if !self.dirty_syntax {
+            return;
+        }
+        match &mut self.syntax_highlight {
+            Some(syntax_highlight) => syntax_highlight.update_syntax(&mut self.lines),
+            None => {
+                // Keep a highlight type for every character, so search results can always be marked
+                for line in &mut self.lines {
+                    line.highlight_types.clear();
+                    line.highlight_types.resize(line.content.chars().count(), HighlightType::Standard);
+                    line.tokens.clear();
+                }
+            }
+        }
+        self.dirty_syntax = false;
+    }
+
+    /// The whole buffer as it would be saved, with lines joined by newlines
+    pub fn contents(&self) -> String {
+        let strings: Vec<String> = self.lines.iter().map(|it| it.content.clone()).collect();
+        let newline = if self.ends_with_newline() {self.newline()} else {""};
+        strings.join(self.newline()) + newline
+    }
+
+    /// Replaces the whole buffer as if it had been loaded, and highlights it again. A newline at the end doesn't start
+    /// another line, but is remembered for saving (and for `contents`) under the preserve policy. The lines are all
+    /// unchanged, and any cursor into the old text needs placing again, as its position may no longer exist
+    pub fn set_text(&mut self, text: &str) {
+        // The new text comes from the buffer's contents, which leave the byte order mark out
+        let bom = self.bom;
+        self.load(Ok(text.to_string()));
+        self.bom |= bom;
+        self.update_syntax();
+    }
+
+    /// Writes the buffer to a file and marks every line as unchanged
+    pub fn save(&mut self, file_name: &String) -> std::io::Result<()> {
+        self.save_range(0..self.lines.len(), file_name)?;
+        for line in &mut self.lines {
+            line.change = LineChange::Unchanged;
+        }
+        Ok(())
+    }
+
+    /// Removes the spaces and tabs from the ends of lines, returning whether there were any
+    pub fn trim_trailing_whitespace(&mut self) -> bool {
+        let mut trimmed = false;
+        for line in &mut self.lines {
+            let len = line.content.trim_end_matches([' ', '\t']).len();
+            if len < line.content.len() {
+                line.content.truncate(len);
+                line.mark_modified();
+                trimmed = true;
+            }
+        }
+        if trimmed {
+            self.changed();
+        }
+        trimmed
+    }
+
+    /// Collapses the blank (or whitespace-only) lines at the end of the buffer into one, so the file ends with a single newline
+    pub fn trim_trailing_blank_lines(&mut self) -> bool {
+        let content_end = self.lines.iter().rposition(|line| !line.content.trim().is_empty()).map_or(0, |index| index + 1);
+        let keep = max(content_end, 1);
+        if keep >= self.lines.len() {
+            return false;
+        }
+        self.lines.truncate(keep);
+        if content_end == 0 {
+            self.lines[0] = Line::blank();
+        }
+        // The blank lines are replaced by a single newline after the last line of content
+        self.final_newline = true;
+        self.changed();
+        true
+    }
+
+    /// Writes some of the lines to a file, leaving the buffer's change markers alone
+    pub fn save_range(&self, range: Range<usize>, file_name: &String) -> std::io::Result<()> {
+        // Writing over the file in place, rather than writing elsewhere and renaming, goes through a symlink to the file
+        // it points at, so the link is left as it was
+        let file = fs::OpenOptions::new().write(true).create(true).truncate(true).open(file_name)?;
+        let mut writer = BufWriter::new(file);
+        let end = min(range.end, self.lines.len());
+        let start = min(range.start, end);
+        if self.bom && start == 0 {
+            writer.write_all(BOM.as_bytes())?;
+        }
+        // Lines are separated by newlines, with one after the last line if the buffer's final newline policy says so.
+        // Part of the buffer ends with one whenever the lines after it would have been on their own lines
+        for (i, line) in self.lines[start..end].iter().enumerate() {
+            if i > 0 {
+                writer.write_all(self.newline().as_bytes())?;
+            }
+            writer.write_all(line.content.as_bytes())?;
+        }
+        let final_newline = match self.final_newline_policy {
+            FinalNewline::Preserve => end < self.lines.len() || self.final_newline,
+            policy => policy == FinalNewline::Always,
+        };
+        if final_newline && start < end {
+            writer.write_all(self.newline().as_bytes())?;
+        }
+        writer.flush()
+    }
+
+    /// Prints part of a line, drawing each marked range of characters with its attribute
+    pub fn print_line(&self, w: &mut impl Write, index: usize, start: usize, width: usize, overflow_column: Option<usize>, marks: &[(Range<usize>, Attribute)]) -> std::io::Result<usize> {
+        match self.lines.get(index) {
+            Some(line) => line.print(w, start, width, &PrintOptions{tab_width: self.tab_width, overflow_column, highlight: &self.syntax_highlight, marks}),
+            None => Ok(0),
+        }
+    }
+
+    /// Where the word the character at `x` is part of starts and ends, if it's part of one
+    pub fn word_bounds(&self, index: usize, x: usize) -> Option<Range<usize>> {
+        let content = &self.lines.get(index)?.content;
+        if !content.get(x..)?.chars().next().is_some_and(is_word_char) {
+            return None;
+        }
+        let start = content[..x].char_indices().rev().take_while(|(_, c)| is_word_char(*c)).last().map_or(x, |(i, _)| i);
+        let end = content[x..].char_indices().find(|(_, c)| !is_word_char(*c)).map_or(content.len(), |(i, _)| x + i);
+        Some(start..end)
+    }
+
+    /// The top-level definitions the syntax highlighter finds, with the line each is on
+    pub fn symbols(&mut self) -> Vec<(String, usize)> {
+        self.update_syntax();
+        self.syntax_highlight.as_ref().map_or(Vec::new(), |syntax_highlight| syntax_highlight.symbols(&self.lines))
+    }
+
+    /// Where `name` is defined by a `fn`, `struct`, `enum`, `trait`, `mod` or `let`, going by the highlighter's tokens as of the
+    /// last `update_syntax`. It's only a guess from the keyword before the name, not a real parse
+    pub fn definitions(&self, name: &str) -> Vec<(usize, usize)> {
+        let mut definitions = Vec::new();
+        for (y, line) in self.lines.iter().enumerate() {
+            let chars: Vec<(usize, char)> = line.content.char_indices().collect();
+            let mut defining = false;
+            for token in &line.tokens {
+                let Some(token_chars) = chars.get(token.start..token.start + token.len) else {
+                    break;
+                };
+                let text: String = token_chars.iter().map(|(_, c)| c).collect();
+                match token.kind {
+                    HighlightType::Keyword if ["fn", "struct", "enum", "trait", "mod", "let"].contains(&text.as_str()) => defining = true,
+                    HighlightType::Keyword if text == "mut" => {}
+                    HighlightType::Standard if text.trim().is_empty() => {}
+                    HighlightType::Identity if defining && text == name => {
+                        definitions.push((token_chars[0].0, y));
+                        defining = false;
+                    }
+                    _ => defining = false,
+                }
+            }
+        }
+        definitions
+    }
+
+    /// The word the character at `x` is part of, if it's part of one
+    pub fn word_at(&self, index: usize, x: usize) -> Option<&str> {
+        let bounds = self.word_bounds(index, x)?;
+        Some(&self.lines[index].content[bounds])
+    }
+
+    fn is_blank(&self, index: usize) -> bool {
+        self.lines[index].content.trim().is_empty()
+    }
+
+    /// The blank line after (or before) the paragraph the line is in or next to, or the first or last line if there isn't one
+    pub fn paragraph_boundary(&self, index: usize, forward: bool) -> usize {
+        let last = self.lines.len() - 1;
+        let step = |index: usize| if forward {min(index + 1, last)} else {index.saturating_sub(1)};
+        let at_end = |index: usize| if forward {index == last} else {index == 0};
+        let mut index = step(index);
+        while !at_end(index) && self.is_blank(index) {
+            index = step(index);
+        }
+        while !at_end(index) && !self.is_blank(index) {
+            index = step(index);
+        }
+        index
+    }
+
+    // How many columns a line's indentation is drawn across, with tabs reaching the next tab stop, or None for a blank
+    // line, which isn't at any level
+    fn indent_width(&self, index: usize) -> Option<usize> {
+        if self.is_blank(index) {
+            return None;
+        }
+        let indent = self.lines[index].content.chars().take_while(|c| *c == ' ' || *c == '\t');
+        Some(indent.fold(0, |column, c| if c == '\t' {column + self.tab_width - column % self.tab_width} else {column + 1}))
+    }
+
+    // The position just after a line's indentation
+    fn indent_end(&self, index: usize) -> (usize, usize) {
+        (self.lines[index].content.chars().take_while(|c| *c == ' ' || *c == '\t').count(), index)
+    }
+
+    /// The start of the nearest line after (or before) the line at `index` that's indented the same, without leaving the
+    /// block it's in, for stepping between the lines of a block in a language structured by indentation
+    pub fn same_indent_line(&self, index: usize, forward: bool) -> Option<(usize, usize)> {
+        let width = self.indent_width(index)?;
+        let others: Vec<usize> = if forward {(index + 1..self.lines.len()).collect()} else {(0..index).rev().collect()};
+        others.into_iter()
+            .filter_map(|other| Some((other, self.indent_width(other)?)))
+            .take_while(|&(_, other_width)| other_width >= width)
+            .find(|&(_, other_width)| other_width == width)
+            .map(|(other, _)| self.indent_end(other))
+    }
+
+    /// The start of the nearest line before the line at `index` that's indented less, which is the line that opens the
+    /// block it's in
+    pub fn parent_indent_line(&self, index: usize) -> Option<(usize, usize)> {
+        let width = self.indent_width(index)?;
+        (0..index).rev()
+            .find(|&other| self.indent_width(other).is_some_and(|other_width| other_width < width))
+            .map(|other| self.indent_end(other))
+    }
+
+    // Where sentences start on a line: after `.`, `!` or `?` and a space, and at the start of a paragraph or after a line ending a sentence
+    fn sentence_starts(&self, index: usize) -> Vec<usize> {
+        let content = &self.lines[index].content;
+        let mut starts = Vec::new();
+        let follows_end = index == 0 || self.is_blank(index - 1) || self.lines[index - 1].content.trim_end().ends_with(['.', '!', '?']);
+        let mut at_start = follows_end;
+        let mut after_terminator = false;
+        for (i, c) in content.char_indices() {
+            if c.is_whitespace() {
+                at_start = at_start || after_terminator;
+            } else if at_start {
+                starts.push(i);
+                at_start = false;
+            }
+            after_terminator = matches!(c, '.' | '!' | '?');
+        }
+        starts
+    }
+
+    /// The start of the next (or previous) sentence from a position, or the end (or start) of the buffer if there isn't one
+    pub fn sentence_start(&self, (x, y): (usize, usize), forward: bool) -> (usize, usize) {
+        if forward {
+            for row in y..self.lines.len() {
+                if let Some(start) = self.sentence_starts(row).into_iter().find(|&start| (row, start) > (y, x)) {
+                    return (start, row);
+                }
+            }
+            let last = self.lines.len() - 1;
+            (self.lines[last].len(), last)
+        } else {
+            for row in (0..=y).rev() {
+                if let Some(start) = self.sentence_starts(row).into_iter().rev().find(|&start| (row, start) < (y, x)) {
+                    return (start, row);
+                }
+            }
+            (0, 0)
+        }
+    }
+
+    pub fn word_occurrences(&self, index: usize, word: &str) -> Vec<Range<usize>> {
+        self.lines.get(index).map_or(Vec::new(), |line| line.word_occurrences(word))
+    }
+
+    pub fn display_column(&self, index: usize, x: usize) -> usize {
+        self.lines.get(index).map_or(0, |line| line.display_column(x, self.tab_width))
+    }
+
+    /// Inserts a character at the cursor and moves the cursor past it
+    pub fn insert_char(&mut self, c: char, cursor: &mut Cursor) {
+        let (x, y) = cursor.get_position();
+        let line = &mut self.lines[cursor.get_line_index()];
+        let pairs = match &self.syntax_highlight {
+            Some(syntax_highlight) if self.auto_pairs && !self.paste_mode => syntax_highlight.auto_pairs(),
+            _ => &[],
+        };
+        let next = line.content[x..].chars().next();
+        match c{
+            // Typing the closing character that's already there steps over it
+            _ if next == Some(c) && pairs.iter().any(|&(_, close)| close == c) => {
+                cursor.set_position(x + c.len_utf8(), y);
+                return;
+            }
+            _ if !next.is_some_and(is_word_char) && pairs.iter().any(|&(open, _)| open == c) => {
+                let close = pairs.iter().find(|&&(open, _)| open == c).map_or(c, |&(_, close)| close);
+                line.insert(x, &format!("{}{}", c, close));
+                cursor.set_position(x + c.len_utf8(), y)
+            }
+            '\t' if self.hard_tabs => {
+                line.insert(x, "\t");
+                cursor.set_position(x + 1, y)
+            }
+            '\t' => {
+                // Pad out to the next indent stop rather than always inserting a full indent
+                let spaces = self.indent_size - line.display_column(x, self.tab_width) % self.indent_size;
+                line.insert(x, &" ".repeat(spaces));
+                cursor.set_position(x + spaces, y)
+            }
+            _ => {
+                line.insert(x, c.to_string().as_str());
+                cursor.set_position(x + c.len_utf8(), y)
+            }
+        }
+        self.changed();
+        if !is_word_char(c) {
+            self.outdent_token(cursor, false);
+        }
+    }
+
+    /// Inserts a character exactly as given, even a tab or control character, and moves the cursor past it
+    pub fn insert_literal(&mut self, c: char, cursor: &mut Cursor) {
+        let (x, y) = cursor.get_position();
+        self.lines[cursor.get_line_index()].insert(x, c.encode_utf8(&mut [0; 4]));
+        cursor.set_position(x + c.len_utf8(), y);
+        self.changed();
+    }
+
+    /// Inserts text that may span several lines at the cursor and moves the cursor past it
+    pub fn insert_str(&mut self, s: &str, cursor: &mut Cursor) {
+        let (x, _) = cursor.get_position();
+        let mut line_index = cursor.get_line_index();
+        let rest = self.lines[line_index].split_at(x);
+        let mut pieces = s.split('\n').map(|piece| piece.strip_suffix('\r').unwrap_or(piece));
+        if let Some(first) = pieces.next() {
+            let line_len = self.lines[line_index].len();
+            self.lines[line_index].insert(line_len, first);
+        }
+        for piece in pieces {
+            let mut line = Line::new(piece.into());
+            line.change = LineChange::Added;
+            line_index += 1;
+            self.lines.insert(line_index, line);
+        }
+        let end = self.lines[line_index].len();
+        self.lines[line_index].append(&rest);
+        self.changed();
+        cursor.set_position(end, line_index);
+    }
+
+    /// Adds `delta` to the integer the cursor is on, or the next one after it on the line, keeping the width of any
+    /// leading zeros. The cursor ends up on its last digit. Returns whether there was a number to change
+    pub fn increment_number(&mut self, cursor: &mut Cursor, delta: i64) -> bool {
+        let (x, y) = cursor.get_position();
+        let content = &self.lines[y].content;
+        let bytes = content.as_bytes();
+        let mut start = min(x, bytes.len());
+        while start > 0 && bytes[start - 1].is_ascii_digit() {
+            start -= 1;
+        }
+        while start < bytes.len() && !bytes[start].is_ascii_digit() {
+            start += 1;
+        }
+        let end = start + bytes[start..].iter().take_while(|byte| byte.is_ascii_digit()).count();
+        let Ok(value) = content[start..end].parse::<i128>() else {
+            return false;
+        };
+        let negative = start > 0 && bytes[start - 1] == b'-';
+        let value = if negative {-value} else {value} + delta as i128;
+        let width = if content[start..end].starts_with('0') {end - start} else {0};
+        let sign = if value < 0 {"-"} else {""};
+        let number = format!("{}{:0width$}", sign, value.unsigned_abs(), width = width);
+        let first = if negative {start - 1} else {start};
+        self.lines[y].content.replace_range(first..end, &number);
+        self.lines[y].mark_modified();
+        self.changed();
+        cursor.set_position(first + number.len() - 1, y);
+        true
+    }
+
+    /// Reverses the order of some of the lines
+    pub fn reverse_lines(&mut self, range: Range<usize>) {
+        let end = min(range.end, self.lines.len());
+        let start = min(range.start, end);
+        self.lines[start..end].reverse();
+        for i in start..end {
+            // A line only counts as changed if what's now in its place is different
+            let mirror = start + end - 1 - i;
+            if self.lines[i].content != self.lines[mirror].content {
+                self.lines[i].mark_modified();
+            }
+        }
+        self.changed();
+    }
+
+    /// Rewrites the indentation of every line as spaces, or as tabs (`indent_size` columns each) with spaces for any
+    /// remainder, keeping each line indented to the same column. Returns how many lines changed
+    pub fn retab(&mut self, to_spaces: bool) -> usize {
+        let mut changed = 0;
+        for line in &mut self.lines {
+            let indent_len = line.content.len() - line.content.trim_start_matches([' ', '\t']).len();
+            let width = line.content[..indent_len].chars().fold(0, |column, c| match c {
+                '\t' => column + self.indent_size - column % self.indent_size,
+                _ => column + 1,
+            });
+            let indent = if to_spaces {
+                " ".repeat(width)
+            } else {
+                format!("{}{}", "\t".repeat(width / self.indent_size), " ".repeat(width % self.indent_size))
+            };
+            if indent != line.content[..indent_len] {
+                line.content.replace_range(..indent_len, &indent);
+                line.mark_modified();
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.changed();
+        }
+        changed
+    }
+
+    /// Removes one level of indentation from the start of a line: a tab, or spaces back to the previous indent stop.
+    /// Returns how many characters went, which is none if the line isn't indented
+    pub fn dedent_line(&mut self, index: usize) -> usize {
+        let line = &mut self.lines[index];
+        let count = if line.content.starts_with('\t') {
+            1
+        } else {
+            let spaces = line.content.len() - line.content.trim_start_matches(' ').len();
+            if spaces == 0 {
+                return 0;
+            }
+            (spaces - 1) % self.indent_size + 1
+        };
+        line.content.replace_range(..count, "");
+        line.mark_modified();
+        self.changed();
+        count
+    }
+
+    /// Splits the line at the cursor, moving the cursor to the start of the new line
+    pub fn new_line(&mut self, cursor: &mut Cursor) {
+        self.outdent_token(cursor, true);
+        let line_index = cursor.get_line_index();
+        let (x,y) = cursor.get_position();
+        let content = &self.lines[line_index].content;
+        let before = content.get(..x).and_then(|before| before.chars().next_back());
+        let after = content.get(x..).and_then(|after| after.chars().next());
+        let brackets = matches!((before, after), (Some('{'), Some('}')) | (Some('('), Some(')')) | (Some('['), Some(']')));
+        if brackets && self.auto_indent && !self.paste_mode {
+            // Between a pair of brackets, the closing one goes on its own line and the cursor on an indented line between
+            let indent: String = content.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+            let mut closing_line = self.lines[line_index].split_at(x);
+            closing_line.content.insert_str(0, &indent);
+            closing_line.change = LineChange::Added;
+            let mut inner_line = Line::new(format!("{}{}", indent, self.indent_step()));
+            inner_line.change = LineChange::Added;
+            let inner_len = inner_line.len();
+            self.lines.insert(line_index + 1, inner_line);
+            self.lines.insert(line_index + 2, closing_line);
+            self.changed();
+            cursor.set_position(inner_len, y + 1);
+            return;
+        }
+        if x == 0 {
+            // Splitting at the start adds a blank line above, rather than moving the whole line down to a new one
+            let mut blank = Line::blank();
+            blank.change = LineChange::Added;
+            self.lines.insert(line_index, blank);
+            self.changed();
+            cursor.set_position(0, y + 1);
+            return;
+        }
+        let mut new_line = self.lines[line_index].split_at(x);
+        new_line.change = LineChange::Added;
+        let mut indent = String::new();
+        if self.auto_indent && !self.paste_mode {
+            let before = &self.lines[line_index].content;
+            indent = before.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+            if self.syntax_highlight.as_ref().is_some_and(|syntax_highlight| syntax_highlight.should_indent_after(before)) {
+                indent.push_str(&self.indent_step());
+            }
+            new_line.content = format!("{}{}", indent, new_line.content.trim_start_matches([' ', '\t']));
+        }
+        self.lines.insert(line_index + 1, new_line);
+        self.changed();
+        cursor.set_position(indent.len(), y + 1);
+    }
+
+    /// Adds a blank line below or above the cursor's line without splitting it, indented like that line (and a step
+    /// further below a line that opens a bracket), and moves the cursor to the end of its indentation
+    pub fn open_line(&mut self, cursor: &mut Cursor, above: bool) {
+        let line_index = cursor.get_line_index();
+        let content = &self.lines[line_index].content;
+        let mut indent: String = content.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        if !above && content.trim_end().ends_with(['{', '(', '[']) {
+            indent.push_str(&self.indent_step());
+        }
+        let mut line = Line::new(indent);
+        line.change = LineChange::Added;
+        let x = line.len();
+        let y = if above {line_index} else {line_index + 1};
+        self.lines.insert(y, line);
+        self.changed();
+        cursor.set_position(x, y);
+    }
+
+    /// Deletes the character before the cursor, joining lines at the start of one
+    pub fn delete_char(&mut self, cursor: &mut Cursor) {
+        let line_index = cursor.get_line_index();
+        let (x, y) = cursor.get_position();
+        let in_indent = self.lines[line_index].content.get(..x).is_some_and(|before| before.chars().all(|c| c == ' '));
+        if x > 0 && in_indent {
+            // Remove spaces back to the previous indent stop
+            let count = (x - 1) % self.indent_size + 1;
+            for i in 1..=count {
+                self.lines[line_index].delete_char(x - i);
+            }
+            cursor.set_position(x - count, y);
+        }else if x > 0 {
+            let previous = self.lines[line_index].previous_boundary(x);
+            self.lines[line_index].delete_char(previous);
+            cursor.set_position(previous, y);
+        }else if y > 0 && self.outdent_before_join && self.lines[line_index].content.starts_with([' ', '\t']) {
+            let line = &mut self.lines[line_index];
+            let indent = line.content.len() - line.content.trim_start_matches([' ', '\t']).len();
+            line.content.replace_range(..indent, "");
+            line.mark_modified();
+        }else if y > 0 {
+            let old_line = self.lines.remove(line_index);
+            let previous = &mut self.lines[line_index-1];
+            let old_length = previous.len();
+            if previous.change == LineChange::Added && previous.content.is_empty() {
+                // Deleting a blank line that was only added leaves the next one as it was
+                *previous = old_line;
+            } else {
+                previous.append(&old_line);
+                // Deleting a line that was only added leaves nothing to mark
+                if old_line.change != LineChange::Added && previous.change == LineChange::Unchanged {
+                    previous.change = LineChange::Removed;
+                }
+            }
+            cursor.set_position(old_length, y-1);
+        }
+        self.changed();
+    }
+
+    fn find_phrase(&self, phrase: &str, index: usize, start: usize) -> Option<usize> {
+        self.lines[index].find_phrase(phrase, start)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Always false, as a buffer keeps at least one line, even if it's blank
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// A line's tokens as of the last `update_syntax`, for features that need to know what the text is, such as whether
+    /// a word is in a comment, without scanning it again
+    pub fn tokens(&self, index: usize) -> &[Token] {
+        self.lines.get(index).map_or(&[], Line::tokens)
+    }
+
+    pub fn get_line(&self, index: usize) -> &str {
+        self.lines[index].content.as_str()
+    }
+
+    /// Counts the lines, words and characters in the buffer
+    pub fn stats(&self) -> TextStats {
+        let mut stats = TextStats{lines: self.lines.len(), words: 0, chars: 0, non_space_chars: 0};
+        for line in &self.lines {
+            let mut in_word = false;
+            for c in line.content.chars() {
+                stats.chars += 1;
+                if c.is_whitespace() {
+                    in_word = false;
+                } else {
+                    stats.non_space_chars += 1;
+                    if !in_word {
+                        stats.words += 1;
+                    }
+                    in_word = true;
+                }
+            }
+        }
+        stats
+    }
+
+    pub fn get_change(&self, index: usize) -> LineChange {
+        self.lines.get(index).map_or(LineChange::Unchanged, |line| line.change)
+    }
+
+    pub fn line_len(&self, index: usize) -> usize {
+        if index < self.len() {
+            self.lines[index].len()
+        } else {
+            0
+        }
+    }
+}
+
+impl Navigable for Text {
+    fn len(&self) -> usize {
+        Text::len(self)
+    }
+
+    fn line_len(&self, index: usize) -> usize {
+        Text::line_len(self, index)
+    }
+
+    fn display_column(&self, index: usize, x: usize) -> usize {
+        Text::display_column(self, index, x)
+    }
+
+    fn position_at_column(&self, index: usize, column: usize) -> usize {
+        let line_len = self.line_len(index);
+        self.lines.get(index).map_or(0, |line| min(line.position_at_column(column, self.tab_width), line_len))
+    }
+
+    fn next_position(&self, index: usize, x: usize) -> usize {
+        self.lines.get(index).map_or(x + 1, |line| line.next_boundary(x))
+    }
+
+    fn previous_position(&self, index: usize, x: usize) -> usize {
+        self.lines.get(index).map_or(x - 1, |line| line.previous_boundary(x))
+    }
+
+    fn clamp_position(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        let y = min(y, self.len().saturating_sub(1));
+        // A position kept from before the text changed can be inside a character now, so it goes back to its start
+        let content = &self.lines[y].content;
+        let x = (0..=min(x, content.len())).rev().find(|&x| content.is_char_boundary(x)).unwrap_or(0);
+        (x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_with(contents: &str) -> Text {
+        let mut text = Text::new();
+        text.set_text(contents);
+        text
+    }
+
+    fn type_str(text: &mut Text, cursor: &mut Cursor, s: &str) {
+        for c in s.chars() {
+            text.insert_char(c, cursor);
+        }
+    }
+
+    #[test]
+    fn typing_after_a_multibyte_character() {
+        let mut text = text_with("");
+        let mut cursor = Cursor::new((80, 24));
+        type_str(&mut text, &mut cursor, "éx");
+        assert_eq!(text.get_line(0), "éx");
+        assert_eq!(cursor.get_position(), (3, 0));
+        assert_eq!(text.display_column(0, 3), 2);
+    }
+
+    #[test]
+    fn moving_right_steps_over_whole_characters() {
+        let mut text = text_with("xéy");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.move_cursor(&text, KeyCode::Right);
+        cursor.move_cursor(&text, KeyCode::Right);
+        assert_eq!(cursor.get_position(), (3, 0));
+        text.insert_char('Z', &mut cursor);
+        assert_eq!(text.get_line(0), "xéZy");
+        cursor.move_cursor(&text, KeyCode::Left);
+        cursor.move_cursor(&text, KeyCode::Left);
+        assert_eq!(cursor.get_position(), (1, 0));
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), "éZy");
+    }
+
+    #[test]
+    fn backspace_removes_a_whole_multibyte_character() {
+        let mut text = text_with("aé");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(3, 0);
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), "a");
+        assert_eq!(cursor.get_position(), (1, 0));
+    }
+
+    #[test]
+    fn one_megabyte_line_without_a_newline() {
+        let line = "aé\t".repeat(1 << 18);
+        let mut text = text_with(&line);
+        assert_eq!(text.len(), 1);
+        let mut cursor = Cursor::new((80, 24));
+        let mut steps = 0;
+        while cursor.get_position().0 < text.line_len(0) {
+            cursor.move_cursor(&text, KeyCode::Right);
+            steps += 1;
+        }
+        assert_eq!(steps, 3 << 18);
+        assert_eq!(cursor.get_position(), (line.len(), 0));
+        cursor.change_offset(&text);
+        let (x_offset, _) = cursor.get_offset();
+        assert_eq!(text.display_column(0, line.len()) - text.display_column(0, x_offset), 79);
+        let mut out = Vec::new();
+        text.print_line(&mut out, 0, x_offset, 80, None, &[]).unwrap();
+        // A search starting part way through a character begins at the next one
+        assert_eq!(text.lines[0].find_phrase("a", 2), Some(2));
+        text.insert_char('z', &mut cursor);
+        assert!(text.get_line(0).ends_with("\tz"));
+
+        // Tabs and control characters are wider than a column, so the view scrolls by what they're drawn across
+        for (line, x_offset) in [("\t".repeat(30), 11), ("\r".repeat(50), 12)] {
+            let text = text_with(&line);
+            let mut cursor = Cursor::new((78, 24));
+            cursor.set_position(line.len(), 0);
+            cursor.change_offset(&text);
+            assert_eq!(cursor.get_offset(), (x_offset, 0));
+            assert!(text.display_column(0, line.len()) - text.display_column(0, x_offset) < 78);
+            cursor.set_position(0, 0);
+            cursor.change_offset(&text);
+            assert_eq!(cursor.get_offset(), (0, 0));
+        }
+    }
+
+    #[test]
+    fn moving_down_onto_multibyte_characters() {
+        let mut text = text_with("abc\néée");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.move_cursor(&text, KeyCode::Right);
+        cursor.move_cursor(&text, KeyCode::Down);
+        assert_eq!(cursor.get_position(), (2, 1));
+        text.insert_char('Z', &mut cursor);
+        assert_eq!(text.get_line(1), "éZée");
+
+        let mut text = text_with("abcdef\nhéllo wörld");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(3, 0);
+        cursor.move_cursor(&text, KeyCode::Down);
+        text.insert_char('Z', &mut cursor);
+        assert_eq!(text.get_line(1), "hélZlo wörld");
+    }
+
+    #[test]
+    fn moving_up_and_down_keeps_the_column_across_short_lines() {
+        let text = text_with("long line here\nab\n\nanother long line");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(9, 0);
+        cursor.move_cursor(&text, KeyCode::Down);
+        assert_eq!(cursor.get_position(), (2, 1));
+        cursor.move_cursor(&text, KeyCode::Down);
+        assert_eq!(cursor.get_position(), (0, 2));
+        cursor.move_cursor(&text, KeyCode::Down);
+        assert_eq!(cursor.get_position(), (9, 3));
+        cursor.move_cursor(&text, KeyCode::Up);
+        cursor.move_cursor(&text, KeyCode::Up);
+        cursor.move_cursor(&text, KeyCode::Up);
+        assert_eq!(cursor.get_position(), (9, 0));
+        // Moving sideways picks a new column
+        cursor.move_cursor(&text, KeyCode::Down);
+        cursor.move_cursor(&text, KeyCode::Left);
+        cursor.move_cursor(&text, KeyCode::Up);
+        assert_eq!(cursor.get_position(), (1, 0));
+    }
+
+    #[test]
+    fn moving_down_keeps_the_column_past_multibyte_characters() {
+        let text = text_with("ééééé\nab\nxxxxxxx");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(8, 0);
+        cursor.move_cursor(&text, KeyCode::Down);
+        assert_eq!(cursor.get_position(), (2, 1));
+        cursor.move_cursor(&text, KeyCode::Down);
+        assert_eq!(cursor.get_position(), (4, 2));
+        cursor.move_cursor(&text, KeyCode::Up);
+        cursor.move_cursor(&text, KeyCode::Up);
+        assert_eq!(cursor.get_position(), (8, 0));
+    }
+
+    #[test]
+    fn searching_past_multibyte_characters() {
+        let mut text = text_with("xéy\nnaïve café, café");
+        let mut search = SearchData::new();
+        assert_eq!(search.find_results(&String::from("y"), &mut text), Some((3, 0)));
+        // The result is a byte offset, drawn one column after the é
+        assert_eq!(text.display_column(0, 3), 2);
+        search.find_results(&String::from("café"), &mut text);
+        assert_eq!(search.result_count(), 2);
+        assert_eq!(search.get_next(), Some(((14, 1), false)));
+        assert_eq!(text.display_column(1, 14), 12);
+    }
+
+    #[test]
+    fn tab_pads_to_the_next_indent_stop() {
+        let mut text = text_with("");
+        let mut cursor = Cursor::new((80, 24));
+        type_str(&mut text, &mut cursor, "ab\t");
+        assert_eq!(text.get_line(0), "ab  ");
+        assert_eq!(cursor.get_position(), (4, 0));
+        type_str(&mut text, &mut cursor, "c\t");
+        assert_eq!(cursor.get_position(), (8, 0));
+
+        let mut text = text_with("");
+        let mut cursor = Cursor::new((80, 24));
+        type_str(&mut text, &mut cursor, "éa\t");
+        assert_eq!(text.get_line(0), "éa  ");
+        assert_eq!(text.display_column(0, cursor.get_position().0), 4);
+    }
+
+    #[test]
+    fn backspace_removes_a_full_indent() {
+        let mut text = text_with("        x");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(8, 0);
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), "    x");
+        assert_eq!(cursor.get_position(), (4, 0));
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), "x");
+        assert_eq!(cursor.get_position(), (0, 0));
+    }
+
+    #[test]
+    fn backspace_removes_a_partial_indent_back_to_the_previous_stop() {
+        let mut text = text_with("      x");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(6, 0);
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), "    x");
+        assert_eq!(cursor.get_position(), (4, 0));
+
+        let mut text = text_with("    x");
+        cursor.set_position(3, 0);
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), " x");
+        assert_eq!(cursor.get_position(), (0, 0));
+    }
+
+    #[test]
+    fn backspace_outside_indentation_removes_one_space() {
+        let mut text = text_with("  ab    cd");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(8, 0);
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), "  ab   cd");
+        assert_eq!(cursor.get_position(), (7, 0));
+    }
+
+    #[test]
+    fn searching_straight_after_an_edit_at_the_end_of_a_line() {
+        let mut text = text_with("hello\nworld");
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(5, 0);
+        type_str(&mut text, &mut cursor, " there");
+        let mut search = SearchData::new();
+        assert_eq!(search.find_results(&String::from("lo there"), &mut text), Some((3, 0)));
+
+        // Edits made while a search is still going leave the highlights short until the next pass
+        cursor.set_position(5, 1);
+        type_str(&mut text, &mut cursor, "wide");
+        let mut search = SearchData::new();
+        search.phrase = String::from("dwide");
+        search.next_row = Some(0);
+        assert_eq!(search.continue_search(&mut text), Some((4, 1)));
+    }
+
+    #[test]
+    fn trailing_blank_lines_collapse_to_one_newline() {
+        let mut text = text_with("a\nb\n\n\n  \n\n");
+        assert!(text.trim_trailing_blank_lines());
+        assert_eq!(text.contents(), "a\nb\n");
+        assert!(!text.trim_trailing_blank_lines());
+
+        // A file of nothing but blank lines keeps a single one
+        let mut text = text_with("\n\n\n");
+        assert!(text.trim_trailing_blank_lines());
+        assert_eq!(text.contents(), "\n");
+    }
+
+    #[test]
+    fn retab_to_spaces() {
+        let mut text = text_with("\tx\n  \ty\n\t\t  z\nno indent\t");
+        assert_eq!(text.retab(true), 3);
+        assert_eq!(text.contents(), "    x\n    y\n          z\nno indent\t");
+        assert_eq!(text.retab(true), 0);
+    }
+
+    #[test]
+    fn retab_to_tabs() {
+        let mut text = text_with("        x\n      y\n  \t z\nno indent  ");
+        assert_eq!(text.retab(false), 3);
+        assert_eq!(text.contents(), "\t\tx\n\t  y\n\t z\nno indent  ");
+        assert_eq!(text.retab(false), 0);
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("text_editor_{}_{}", std::process::id(), name)).display().to_string()
+    }
+
+    fn changes(text: &Text) -> Vec<char> {
+        (0..text.len()).map(|index| match text.get_change(index) {
+            LineChange::Unchanged => ' ',
+            LineChange::Added => '+',
+            LineChange::Modified => '~',
+            LineChange::Removed => '-',
+        }).collect()
+    }
+
+    #[test]
+    fn change_markers_for_added_modified_and_removed_lines() {
+        let mut text = text_with("one\ntwo\n\nthree");
+        let mut cursor = Cursor::new((80, 24));
+        assert_eq!(changes(&text), [' ', ' ', ' ', ' ']);
+
+        // Editing a line marks it modified, however many edits it has
+        cursor.set_position(3, 0);
+        type_str(&mut text, &mut cursor, "!!");
+        assert_eq!(changes(&text), ['~', ' ', ' ', ' ']);
+
+        // A new line is added, without marking the one it was split from
+        text.new_line(&mut cursor);
+        assert_eq!(changes(&text), ['~', '+', ' ', ' ', ' ']);
+
+        // Deleting the added line again leaves no trace of it
+        text.delete_char(&mut cursor);
+        assert_eq!(changes(&text), ['~', ' ', ' ', ' ']);
+
+        // Deleting a blank line from the file marks where it was removed from
+        cursor.set_position(0, 2);
+        text.delete_char(&mut cursor);
+        assert_eq!(changes(&text), ['~', '-', ' ']);
+
+        // Joining a line onto the one before changes that one
+        cursor.set_position(0, 2);
+        text.delete_char(&mut cursor);
+        assert_eq!(changes(&text), ['~', '~']);
+        assert_eq!(text.get_line(1), "twothree");
+
+        // Saving makes the buffer the new version to compare with
+        let path = temp_path("changes.txt");
+        text.save(&path).unwrap();
+        assert_eq!(changes(&text), [' ', ' ']);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn final_newline_round_trips() {
+        let path = temp_path("final_newline.txt");
+        let cases = [
+            (FinalNewline::Preserve, ["a", "a\n", "a\n\n"]),
+            (FinalNewline::Always, ["a\n", "a\n", "a\n\n"]),
+            (FinalNewline::Never, ["a", "a", "a\n"]),
+        ];
+        for (policy, expected) in cases {
+            for (contents, expected) in ["a", "a\n", "a\n\n"].into_iter().zip(expected) {
+                let mut text = Text::new();
+                text.set_final_newline_policy(policy);
+                text.load(Ok(contents.to_string()));
+                text.save(&path).unwrap();
+                assert_eq!(fs::read_to_string(&path).unwrap(), expected);
+                assert_eq!(text.contents(), expected);
+            }
+        }
+        // A new file gets a final newline under the preserve policy
+        let mut text = Text::new();
+        let mut cursor = Cursor::new((80, 24));
+        text.insert_char('a', &mut cursor);
+        text.save(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\n");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn reversing_a_range_of_lines() {
+        let mut text = text_with("a\nb\nc\nd\ne");
+        text.reverse_lines(1..4);
+        assert_eq!((0..text.len()).map(|index| text.get_line(index)).collect::<Vec<_>>(), ["a", "d", "c", "b", "e"]);
+        // The middle line stays where it was, so it isn't marked as changed
+        assert_eq!(changes(&text), [' ', '~', ' ', '~', ' ']);
+    }
+
+    #[test]
+    fn scrolling_sideways_while_moving_up_and_down() {
+        let long = "x".repeat(30);
+        let text = text_with(&format!("{long}\n{}\nabc\n{long}", "y".repeat(20)));
+        let mut cursor = Cursor::new((10, 24));
+        cursor.set_position(25, 0);
+        cursor.change_offset(&text);
+        assert_eq!(cursor.get_offset(), (16, 0));
+
+        let mut step = |direction| {
+            cursor.move_cursor(&text, direction);
+            cursor.change_offset(&text);
+            (cursor.get_position(), cursor.get_offset().0)
+        };
+        // The end of a shorter line that's still in view doesn't scroll
+        assert_eq!(step(KeyCode::Down), ((20, 1), 16));
+        // One out of view does, only as far as it has to
+        assert_eq!(step(KeyCode::Down), ((3, 2), 3));
+        // Back on a long line the earlier scroll is returned to, rather than only scrolling enough to show the cursor
+        assert_eq!(step(KeyCode::Down), ((25, 3), 16));
+        assert_eq!(step(KeyCode::Up), ((3, 2), 3));
+        assert_eq!(step(KeyCode::Up), ((20, 1), 16));
+    }
+
+    #[test]
+    fn backspace_at_the_start_of_an_indented_line_outdents_before_joining() {
+        let mut text = text_with("a\n  \tb");
+        text.set_outdent_before_join(true);
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(0, 1);
+        text.delete_char(&mut cursor);
+        assert_eq!((text.get_line(0), text.get_line(1)), ("a", "b"));
+        assert_eq!(cursor.get_position(), (0, 1));
+        // With the indentation gone, pressing it again joins the lines
+        text.delete_char(&mut cursor);
+        assert_eq!(text.len(), 1);
+        assert_eq!(text.get_line(0), "ab");
+        assert_eq!(cursor.get_position(), (1, 0));
+
+        // Without the option the indentation is joined on as it is
+        let mut text = text_with("a\n  \tb");
+        cursor.set_position(0, 1);
+        text.delete_char(&mut cursor);
+        assert_eq!(text.get_line(0), "a  \tb");
+        assert_eq!(cursor.get_position(), (1, 0));
+    }
+
+    #[test]
+    fn insert_positions_at_the_end_of_a_line_and_of_the_text() {
+        let mut text = text_with("ab\nc");
+        let mut cursor = Cursor::new((80, 24));
+        assert_eq!(text.clamp_position((9, 0)), (2, 0));
+        assert_eq!(text.clamp_position((9, 9)), (1, 1));
+
+        // The end of a line is a position of its own, before moving right goes on to the next line
+        cursor.move_cursor(&text, KeyCode::Right);
+        cursor.move_cursor(&text, KeyCode::Right);
+        assert_eq!(cursor.get_position(), (2, 0));
+        cursor.move_cursor(&text, KeyCode::Right);
+        assert_eq!(cursor.get_position(), (0, 1));
+
+        // At the end of the text moving on goes nowhere, and typing appends
+        cursor.move_cursor(&text, KeyCode::Right);
+        cursor.move_cursor(&text, KeyCode::Right);
+        cursor.move_cursor(&text, KeyCode::Down);
+        assert_eq!(cursor.get_position(), (1, 1));
+        type_str(&mut text, &mut cursor, "d");
+        assert_eq!(text.get_line(1), "cd");
+
+        // Moving up keeps to the end of the line above, and typing there appends to it too
+        cursor.move_cursor(&text, KeyCode::Up);
+        assert_eq!(cursor.get_position(), (2, 0));
+        type_str(&mut text, &mut cursor, "!");
+        assert_eq!((text.get_line(0), text.get_line(1)), ("ab!", "cd"));
+    }
+
+    #[test]
+    fn marks_are_byte_ranges_on_lines_with_multibyte_characters() {
+        let text = text_with("é ab xab ab");
+        assert_eq!(text.word_occurrences(0, "ab"), [3..5, 10..12]);
+
+        // Only the "a", at bytes 2..3, is marked, so the text is printed in three runs around it
+        let text = text_with("éab");
+        let mut output = Vec::new();
+        text.print_line(&mut output, 0, 0, 80, None, &[(2..3, Attribute::Reverse)]).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains('é') && !output.contains("éa") && !output.contains("ab"));
+    }
+
+    #[test]
+    fn dedenting_a_line_by_one_level() {
+        let mut text = text_with("\t\tx\n      y\nz");
+        assert_eq!(text.dedent_line(0), 1);
+        assert_eq!(text.get_line(0), "\tx");
+        // Spaces go back to the previous indent stop
+        assert_eq!(text.dedent_line(1), 2);
+        assert_eq!(text.get_line(1), "    y");
+        assert_eq!(text.dedent_line(2), 0);
+        assert_eq!(changes(&text), ['~', '~', ' ']);
+    }
+
+    #[test]
+    fn clamping_the_cursor_after_the_text_shrinks() {
+        let mut text = text_with(&vec!["x".repeat(100); 100].join("\n"));
+        let mut cursor = Cursor::new((20, 10));
+        cursor.set_position(90, 80);
+        cursor.change_offset(&text);
+        assert_eq!(cursor.get_offset(), (71, 71));
+
+        text.load(Ok(String::from("short\nlines\n")));
+        cursor.clamp_to(&text);
+        assert_eq!(cursor.get_position(), (5, 1));
+        assert_eq!(cursor.get_offset(), (5, 1));
+
+        // A position that's now inside a character goes back to the start of it
+        text.load(Ok(String::from("aéé\n")));
+        cursor.set_position(4, 0);
+        cursor.clamp_to(&text);
+        assert_eq!(cursor.get_position(), (3, 0));
+        type_str(&mut text, &mut cursor, "!");
+        assert_eq!(text.get_line(0), "aé!é");
+    }
+
+    #[test]
+    fn control_characters_are_drawn_in_caret_notation() {
+        let text = text_with("a\rb\x1bc");
+        assert_eq!(text.display_column(0, text.line_len(0)), 7);
+        let mut output = Vec::new();
+        assert_eq!(text.print_line(&mut output, 0, 0, 80, None, &[]).unwrap(), 7);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("^M") && output.contains("^["));
+        // Nothing from the line reaches the terminal raw, so the only escapes are the ones starting colour changes
+        assert!(!output.contains('\r'));
+        assert!(output.match_indices('\x1b').all(|(i, _)| output[i + 1..].starts_with('[')));
+    }
+
+    #[test]
+    fn byte_order_mark_round_trips() {
+        let path = temp_path("bom.txt");
+        for contents in ["\u{feff}héllo\nwörld\n", "\u{feff}a\r\nb", "\u{feff}", "no mark\n"] {
+            fs::write(&path, contents).unwrap();
+            let mut text = Text::new();
+            text.load(fs::read_to_string(&path));
+            assert_eq!(text.has_bom(), contents.starts_with('\u{feff}'));
+            assert!(!text.get_line(0).starts_with('\u{feff}'));
+            text.save(&path).unwrap();
+            assert_eq!(fs::read(&path).unwrap(), contents.as_bytes());
+        }
+
+        // Editing the first line keeps the mark in front of it
+        fs::write(&path, "\u{feff}ab\n").unwrap();
+        let mut text = Text::new();
+        text.load(fs::read_to_string(&path));
+        let mut cursor = Cursor::new((80, 24));
+        type_str(&mut text, &mut cursor, "x");
+        text.save(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "\u{feff}xab\n");
+        let _ = fs::remove_file(path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saving_through_a_symlink_keeps_the_link() {
+        let root = std::env::temp_dir().join(format!("text_editor_{}_symlink", std::process::id()));
+        let (target_dir, link_dir) = (root.join("target"), root.join("link"));
+        fs::create_dir_all(&target_dir).unwrap();
+        fs::create_dir_all(&link_dir).unwrap();
+        let (target, link) = (target_dir.join("file.txt"), link_dir.join("file.txt"));
+        fs::write(&target, "old\n").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let link_name = link.display().to_string();
+        let mut text = Text::new();
+        text.load(fs::read_to_string(&link_name));
+        let mut cursor = Cursor::new((80, 24));
+        type_str(&mut text, &mut cursor, "new ");
+        text.save(&link_name).unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link).unwrap(), target);
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new old\n");
+        assert_eq!(fs::read_dir(&link_dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn the_revision_only_changes_with_the_lines() {
+        let mut text = text_with("ab\ncd");
+        let mut cursor = Cursor::new((80, 24));
+        let revision = text.revision();
+        cursor.move_cursor(&text, KeyCode::Down);
+        text.set_syntax(None);
+        text.update_syntax();
+        assert_eq!(text.revision(), revision);
+        text.insert_char('x', &mut cursor);
+        assert!(text.revision() > revision);
+        let revision = text.revision();
+        text.trim_trailing_whitespace();
+        assert_eq!(text.revision(), revision);
+    }
+
+    #[test]
+    fn paste_mode_leaves_new_lines_as_typed() {
+        let mut text = text_with("    f() {}");
+        text.set_auto_indent(true);
+        text.set_paste_mode(true);
+        let mut cursor = Cursor::new((80, 24));
+        cursor.set_position(9, 0);
+        text.new_line(&mut cursor);
+        assert_eq!((0..text.len()).map(|index| text.get_line(index)).collect::<Vec<_>>(), ["    f() {", "}"]);
+        assert_eq!(cursor.get_position(), (0, 1));
+
+        // Out of paste mode the brackets are opened up around an indented line
+        let mut text = text_with("    f() {}");
+        text.set_auto_indent(true);
+        cursor.set_position(9, 0);
+        text.new_line(&mut cursor);
+        assert_eq!((0..text.len()).map(|index| text.get_line(index)).collect::<Vec<_>>(), ["    f() {", "        ", "    }"]);
+        assert_eq!(cursor.get_position(), (8, 1));
     }
 }
\ No newline at end of file
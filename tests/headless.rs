@@ -0,0 +1,173 @@
+use std::{env, fs, io, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use text_editor::{config::Config, editor::Editor, lint};
+
+fn editor() -> Editor<Vec<u8>> {
+    Editor::headless(Vec::new(), (80, 24))
+}
+
+fn keys(s: &str) -> Vec<KeyEvent> {
+    s.chars().map(|c| match c {
+        '\n' => KeyEvent::from(KeyCode::Enter),
+        c => KeyEvent::from(KeyCode::Char(c)),
+    }).collect()
+}
+
+fn ctrl(c: char) -> KeyEvent {
+    KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+}
+
+// Runs the editor until its script runs out
+fn play(editor: &mut Editor<Vec<u8>>, script: impl IntoIterator<Item = KeyEvent>) {
+    editor.push_keys(script);
+    let error = editor.run().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+fn lines(editor: &Editor<Vec<u8>>) -> Vec<&str> {
+    (0..editor.text().len()).map(|index| editor.text().get_line(index)).collect()
+}
+
+// A file in a directory of its own, so tests running at the same time don't share one
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("text_editor_headless_{}_{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn typing_and_new_lines() {
+    let mut editor = editor();
+    play(&mut editor, keys("hello\nworld"));
+    assert_eq!(lines(&editor), ["hello", "world"]);
+    assert_eq!(editor.cursor().get_position(), (5, 1));
+}
+
+#[test]
+fn handling_keys_one_at_a_time() {
+    let mut editor = editor();
+    for key in keys("abc") {
+        editor.handle_key(key).unwrap();
+    }
+    editor.handle_key(KeyEvent::from(KeyCode::Left)).unwrap();
+    editor.handle_key(KeyEvent::from(KeyCode::Backspace)).unwrap();
+    assert_eq!(lines(&editor), ["ac"]);
+    assert!(editor.is_running());
+}
+
+#[test]
+fn keys_after_opening_a_file_are_kept() {
+    let path = temp_file("open.txt", "first\nsecond\n");
+    let mut editor = editor();
+    let mut script = vec![ctrl('l')];
+    script.extend(keys(&format!("{}\n", path.display())));
+    script.extend(keys("> "));
+    play(&mut editor, script);
+    assert_eq!(lines(&editor), ["> first", "second"]);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn saving_writes_the_buffer() {
+    let path = temp_file("save.txt", "old\n");
+    let mut editor = editor();
+    let mut script = vec![ctrl('l')];
+    script.extend(keys(&format!("{}\n", path.display())));
+    script.extend([KeyEvent::from(KeyCode::Right); 3]);
+    script.extend(keys(" and new"));
+    // Saving offers the open file's name
+    script.push(ctrl('s'));
+    script.push(KeyEvent::from(KeyCode::Enter));
+    play(&mut editor, script);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "old and new\n");
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn drawing_into_a_buffer() {
+    let mut editor = editor();
+    play(&mut editor, keys("drawn"));
+    editor.refresh_screen().unwrap();
+    let screen = String::from_utf8_lossy(editor.writer()).into_owned();
+    assert!(screen.contains("drawn"));
+}
+
+#[test]
+fn saving_keeps_a_backup_of_the_old_contents() {
+    let path = temp_file("backup.txt", "before\n");
+    let mut config = Config::new();
+    config.backup = true;
+    let mut editor = Editor::headless_with_config(Vec::new(), (80, 24), config);
+    let mut script = vec![ctrl('l')];
+    script.extend(keys(&format!("{}\n", path.display())));
+    script.extend(keys("after "));
+    script.push(ctrl('s'));
+    script.push(KeyEvent::from(KeyCode::Enter));
+    play(&mut editor, script);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "after before\n");
+    assert_eq!(fs::read_to_string(format!("{}.bak", path.display())).unwrap(), "before\n");
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn a_one_by_one_terminal_does_not_crash() {
+    let mut editor = Editor::headless(Vec::new(), (1, 1));
+    play(&mut editor, keys("ab
+c"));
+    assert_eq!(lines(&editor), ["ab", "c"]);
+    editor.refresh_screen().unwrap();
+    let screen = String::from_utf8_lossy(editor.writer()).into_owned();
+    assert!(!screen.contains("ab"));
+
+    // A window that is merely too small says so, cut to its width
+    let mut editor = Editor::headless(Vec::new(), (10, 3));
+    play(&mut editor, keys("ab"));
+    let screen = String::from_utf8_lossy(editor.writer()).into_owned();
+    assert!(screen.contains("Terminal t"));
+    assert!(!screen.contains("Terminal to"));
+}
+
+#[test]
+fn reversing_the_selected_lines() {
+    let mut editor = editor();
+    let mut script = keys("one\ntwo\nthree\nfour");
+    script.push(ctrl('p'));
+    script.extend(keys("reverse\n2-4\n"));
+    play(&mut editor, script);
+    assert_eq!(lines(&editor), ["one", "four", "three", "two"]);
+    assert_eq!(editor.cursor().get_position(), (0, 1));
+}
+
+#[test]
+fn shift_tab_dedents_the_line() {
+    let mut editor = editor();
+    let mut script = vec![KeyEvent::from(KeyCode::Tab), KeyEvent::from(KeyCode::Tab)];
+    script.extend(keys("x"));
+    script.push(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT));
+    play(&mut editor, script);
+    assert_eq!(lines(&editor), ["    x"]);
+    assert_eq!(editor.cursor().get_position(), (5, 0));
+}
+
+#[test]
+fn a_file_that_is_not_utf8_opens_read_only() {
+    let path = temp_file("latin1.txt", "");
+    fs::write(&path, b"caf\xe9 au lait\n").unwrap();
+    let mut editor = editor();
+    let mut script = vec![ctrl('l')];
+    script.extend(keys(&format!("{}\n", path.display())));
+    script.extend(keys("x"));
+    script.push(ctrl('s'));
+    script.push(KeyEvent::from(KeyCode::Enter));
+    play(&mut editor, script);
+    assert_eq!(lines(&editor), ["caf\u{FFFD} au lait"]);
+    // Neither typing nor saving writes the replacement character over the byte it stands for
+    assert_eq!(fs::read(&path).unwrap(), b"caf\xe9 au lait\n");
+    let issues = lint::lint(editor.text());
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].line, 0);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
\ No newline at end of file